@@ -2,6 +2,7 @@ pub const ENOENT: u32 = 2;
 pub const ENOMEM: u32 = 12;
 pub const EINVAL: u32 = 22;
 pub const ERANGE: u32 = 34;
+pub const ENODATA: u32 = 61;
 pub const ENOTSUP: u32 = 95;
 pub const ACL_READ: u32 = 4;
 pub const ACL_WRITE: u32 = 2;
@@ -17,6 +18,8 @@ pub const ACL_TYPE_ACCESS: u32 = 32768;
 pub const ACL_TYPE_DEFAULT: u32 = 16384;
 pub const ACL_FIRST_ENTRY: u32 = 0;
 pub const ACL_NEXT_ENTRY: u32 = 1;
+pub const O_NOFOLLOW: u32 = 131072;
+pub const O_PATH: u32 = 2097152;
 pub const ACL_MULTI_ERROR: u32 = 4096;
 pub const ACL_DUPLICATE_ERROR: u32 = 8192;
 pub const ACL_MISS_ERROR: u32 = 12288;
@@ -245,3 +248,11 @@ extern "C" {
         __result: *mut *mut passwd,
     ) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn getxattr(
+        __path: *const ::std::os::raw::c_char,
+        __name: *const ::std::os::raw::c_char,
+        __value: *mut ::std::os::raw::c_void,
+        __size: usize,
+    ) -> isize;
+}
@@ -5,6 +5,7 @@ pub const ERANGE: u32 = 34;
 pub const ENOTSUP: u32 = 45;
 pub const ACL_MAX_ENTRIES: u32 = 128;
 pub const O_SYMLINK: u32 = 2097152;
+pub const _PC_EXTENDED_SECURITY_NP: u32 = 26;
 pub const ID_TYPE_UID: u32 = 0;
 pub const ID_TYPE_GID: u32 = 1;
 pub type __uint32_t = ::std::os::raw::c_uint;
@@ -369,3 +370,9 @@ extern "C" {
 extern "C" {
     pub fn close(arg1: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn pathconf(
+        arg1: *const ::std::os::raw::c_char,
+        arg2: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_long;
+}
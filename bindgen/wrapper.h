@@ -1,3 +1,7 @@
+#if __linux__
+// Needed for O_PATH.
+# define _GNU_SOURCE
+#endif
 #include <sys/types.h>
 #include <sys/errno.h>
 #include <sys/acl.h>
@@ -8,6 +12,8 @@
 #elif __linux__
 // Linux supplies non-standard ACL extensions in a different header.
 # include <acl/libacl.h>
+// Needed to probe for ACL support.
+# include <sys/xattr.h>
 #endif
 #include <grp.h>
 #include <pwd.h>
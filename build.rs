@@ -63,6 +63,10 @@ fn bindgen_bindings(wrapper: &str, out_path: &Path) {
         "pathconf",
         #[cfg(target_os = "freebsd")]
         "lpathconf",
+        #[cfg(target_os = "macos")]
+        "pathconf",
+        #[cfg(target_os = "linux")]
+        "getxattr",
     ];
     let vars = [
         "ACL_.*",
@@ -74,6 +78,16 @@ fn bindgen_bindings(wrapper: &str, out_path: &Path) {
         "ERANGE",
         #[cfg(target_os = "macos")]
         "O_SYMLINK",
+        #[cfg(target_os = "linux")]
+        "O_PATH",
+        #[cfg(target_os = "linux")]
+        "O_NOFOLLOW",
+        #[cfg(target_os = "linux")]
+        "ENODATA",
+        #[cfg(target_os = "freebsd")]
+        "_PC_ACL_EXTENDED",
+        #[cfg(target_os = "macos")]
+        "_PC_EXTENDED_SECURITY_NP",
         "ID_TYPE_UID",
         "ID_TYPE_GID",
     ];
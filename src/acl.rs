@@ -6,8 +6,11 @@ use crate::aclentry::AclEntryKind;
 use crate::failx::{fail_custom, path_err};
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 use crate::flag::Flag;
+use crate::names::NameCache;
+use crate::nofollow::with_path_no_follow;
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 use crate::perm::Perm;
+use crate::raw::RawAclEntry;
 use crate::util::*;
 
 use bitflags::bitflags;
@@ -28,6 +31,17 @@ bitflags! {
         /// Get/set the ACL of the symlink itself (macOS only).
         const SYMLINK_ACL = 0b0100;
 
+        /// Restore every path already changed if `setfacl` fails.
+        const TRANSACTIONAL = 0b1000;
+
+        /// Fail if any component of the path is a symlink (Linux only).
+        const NO_FOLLOW = 0b10_0000;
+
+        /// Drop entries of kind `Unknown` when setting an ACL, and log a
+        /// warning for each one. Use [`split_unknown`](crate::split_unknown)
+        /// to get the entries.
+        const DROP_UNKNOWN = 0b100_0000;
+
         /// Ignore expected error when using DEFAULT_ACL on a file.
         #[doc(hidden)]
         const IGNORE_EXPECTED_FILE_ERR = 0b10000;
@@ -68,6 +82,12 @@ impl Acl {
     ///
     /// Returns an [`io::Error`] on failure.
     pub fn read(path: &Path, options: AclOption) -> io::Result<Acl> {
+        if options.contains(AclOption::NO_FOLLOW) {
+            return with_path_no_follow(path, |path| {
+                Acl::read(path, options - AclOption::NO_FOLLOW)
+            });
+        }
+
         let symlink_acl = options.contains(AclOption::SYMLINK_ACL);
         let default_acl = options.contains(AclOption::DEFAULT_ACL);
 
@@ -101,6 +121,12 @@ impl Acl {
     ///
     /// Returns an [`io::Error`] on failure.
     pub fn write(&self, path: &Path, options: AclOption) -> io::Result<()> {
+        if options.contains(AclOption::NO_FOLLOW) {
+            return with_path_no_follow(path, |path| {
+                self.write(path, options - AclOption::NO_FOLLOW)
+            });
+        }
+
         let symlink_acl = options.contains(AclOption::SYMLINK_ACL);
         let default_acl = options.contains(AclOption::DEFAULT_ACL);
 
@@ -311,11 +337,22 @@ impl Acl {
     /// # Errors
     ///
     /// Returns an [`io::Error`] on failure.
+    #[cfg(test)]
     pub fn entries(&self) -> io::Result<Vec<AclEntry>> {
+        self.entries_cached(None)
+    }
+
+    /// Return ACL as a vector of [`AclEntry`], looking up names through
+    /// `names` if provided.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn entries_cached(&self, names: Option<&NameCache>) -> io::Result<Vec<AclEntry>> {
         let mut entries = Vec::<AclEntry>::with_capacity(8);
 
         xacl_foreach(self.acl, |entry_p| {
-            let entry = AclEntry::from_raw(entry_p, self.acl)?;
+            let entry = AclEntry::from_raw(entry_p, self.acl, names)?;
             entries.push(entry);
             Ok(())
         })?;
@@ -331,6 +368,24 @@ impl Acl {
         Ok(entries)
     }
 
+    /// Return the first entry of the ACL, or the entry after the one returned
+    /// by the previous call, without looking up names.
+    pub fn next_raw_entry(&mut self, first: bool) -> Option<io::Result<RawAclEntry>> {
+        let entry_p = xacl_next_entry(self.acl, first)?;
+
+        #[allow(unused_mut)]
+        let mut result = RawAclEntry::from_raw(entry_p, self.acl);
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        if self.default_acl {
+            if let Ok(entry) = &mut result {
+                entry.flags |= Flag::DEFAULT;
+            }
+        }
+
+        Some(result)
+    }
+
     /// Return ACL as a string.
     ///
     /// This method is provided as a tracing/debugging aid.
@@ -345,7 +400,7 @@ impl Acl {
         for entry in self.entries()? {
             writeln!(buf, "{entry}")?;
         }
-        String::from_utf8(buf).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+        String::from_utf8(buf).map_err(io::Error::other)
     }
 
     /// Return true if ACL is empty.
@@ -390,7 +445,7 @@ fn is_non_directory(path: &Path, symlink: bool) -> bool {
         path.metadata()
     };
 
-    result.map_or(false, |meta| !meta.is_dir())
+    result.is_ok_and(|meta| !meta.is_dir())
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -3,8 +3,10 @@
 use crate::failx::fail_custom;
 use crate::flag::Flag;
 use crate::format;
+use crate::names::NameCache;
 use crate::perm::Perm;
 use crate::qualifier::Qualifier;
+use crate::raw::RawAclEntry;
 use crate::util::*;
 
 #[cfg(feature = "serde")]
@@ -194,41 +196,15 @@ impl AclEntry {
     }
 
     /// Return an `AclEntry` constructed from a native `acl_entry_t`.
-    pub(crate) fn from_raw(entry: acl_entry_t, acl: acl_t) -> io::Result<AclEntry> {
-        let (allow, qualifier, perms, flags) = xacl_get_entry(acl, entry)?;
-
-        let (kind, name) = match qualifier {
-            Qualifier::Unknown(s) => (AclEntryKind::Unknown, s),
-
-            #[cfg(target_os = "macos")]
-            Qualifier::User(_) | Qualifier::Guid(_) => (AclEntryKind::User, qualifier.name()?),
-
-            #[cfg(target_os = "macos")]
-            Qualifier::Group(_) => (AclEntryKind::Group, qualifier.name()?),
-
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::User(_) | Qualifier::UserObj => (AclEntryKind::User, qualifier.name()?),
-
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::Group(_) | Qualifier::GroupObj => (AclEntryKind::Group, qualifier.name()?),
-
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::Mask => (AclEntryKind::Mask, qualifier.name()?),
-
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::Other => (AclEntryKind::Other, qualifier.name()?),
-
-            #[cfg(target_os = "freebsd")]
-            Qualifier::Everyone => (AclEntryKind::Everyone, qualifier.name()?),
-        };
-
-        Ok(AclEntry {
-            kind,
-            name,
-            perms,
-            flags,
-            allow,
-        })
+    ///
+    /// If `names` is provided, user and group names are looked up through the
+    /// cache.
+    pub(crate) fn from_raw(
+        entry: acl_entry_t,
+        acl: acl_t,
+        names: Option<&NameCache>,
+    ) -> io::Result<AclEntry> {
+        RawAclEntry::from_raw(entry, acl)?.to_entry_cached(names)
     }
 
     pub(crate) fn add_to_acl(&self, acl: &mut acl_t) -> io::Result<()> {
@@ -238,7 +214,7 @@ impl AclEntry {
         Ok(())
     }
 
-    fn qualifier(&self) -> io::Result<Qualifier> {
+    pub(crate) fn qualifier(&self) -> io::Result<Qualifier> {
         let qualifier = match self.kind {
             AclEntryKind::User => Qualifier::user_named(&self.name)?,
             AclEntryKind::Group => Qualifier::group_named(&self.name)?,
@@ -292,7 +268,7 @@ impl fmt::Display for AclEntry {
     }
 }
 
-fn parse_allow(value: &str) -> Result<bool, format::Error> {
+pub(crate) fn parse_allow(value: &str) -> Result<bool, format::Error> {
     let result = match value {
         "allow" => true,
         "deny" => false,
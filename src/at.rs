@@ -0,0 +1,230 @@
+//! Implements ACL calls relative to an open directory (Linux only).
+
+use crate::acl::AclOption;
+use crate::aclentry::AclEntry;
+use crate::failx::{fail_custom, path_err, PathError};
+use crate::nofollow::symlink_err;
+use crate::sys::sg;
+use crate::{getfacl, setfacl};
+
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Component, Path, PathBuf};
+
+/// Get the access control list (ACL) of `name` in the directory `dir`.
+///
+/// `name` must be a single path component, or "." for `dir` itself. The file
+/// is opened relative to `dir` without following a symlink, and the ACL is
+/// read through the open file, so the path is never resolved again. If
+/// `name` is a symlink, `getfacl_at` fails with an error that wraps a
+/// [`SymlinkError`](crate::SymlinkError).
+///
+/// See [`getfacl`] for a description of `options`. [`AclOption::NO_FOLLOW`]
+/// is accepted, and has no further effect.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::getfacl_at;
+///
+/// let dir = std::fs::File::open("./tmp")?;
+/// let entries = getfacl_at(&dir, "foo", None)?;
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn getfacl_at<D, P, O>(dir: D, name: P, options: O) -> io::Result<Vec<AclEntry>>
+where
+    D: AsFd,
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    // The file is already opened without following a symlink, and the path
+    // in `/proc/self/fd` is itself a symlink.
+    let options = options.into().map(|options| options - AclOption::NO_FOLLOW);
+    with_file_at(dir.as_fd(), name.as_ref(), |path| getfacl(path, options))
+}
+
+/// Set the access control list (ACL) of `name` in the directory `dir`.
+///
+/// `name` must be a single path component, or "." for `dir` itself. The file
+/// is opened relative to `dir` without following a symlink, and the ACL is
+/// written through the open file, so the path is never resolved again. If
+/// `name` is a symlink, `setfacl_at` fails with an error that wraps a
+/// [`SymlinkError`](crate::SymlinkError).
+///
+/// See [`setfacl`] for a description of `entries` and `options`.
+/// [`AclOption::NO_FOLLOW`] is accepted, and has no further effect.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn setfacl_at<D, P, O>(dir: D, name: P, entries: &[AclEntry], options: O) -> io::Result<()>
+where
+    D: AsFd,
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    // The file is already opened without following a symlink, and the path
+    // in `/proc/self/fd` is itself a symlink.
+    let options = options.into().map(|options| options - AclOption::NO_FOLLOW);
+    with_file_at(dir.as_fd(), name.as_ref(), |path| {
+        setfacl(&[path], entries, options)
+    })
+}
+
+/// Open `name` relative to `dir` and call `func` with a path that refers to
+/// the open file.
+fn with_file_at<D, T, F>(dir: D, name: &Path, func: F) -> io::Result<T>
+where
+    D: AsRawFd,
+    F: FnOnce(&Path) -> io::Result<T>,
+{
+    // Path of the directory, for error messages only.
+    let dir_display = fs::read_link(fd_path(&dir)).unwrap_or_default();
+
+    let mut components = name.components();
+    let name = match (components.next(), components.next()) {
+        (Some(Component::CurDir), None) => return with_fd_path(&dir, &dir_display, func),
+        (Some(Component::Normal(name)), None) => name,
+        _ => return fail_custom(&format!("invalid name: {name:?}")),
+    };
+    let display = dir_display.join(name);
+
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(sg::O_PATH | sg::O_NOFOLLOW)
+        .open(fd_path(&dir).join(name))
+        .map_err(|err| path_err(&display, &err))?;
+
+    if file.metadata()?.is_symlink() {
+        return Err(symlink_err(&display, &display));
+    }
+
+    with_fd_path(&file, &display, func)
+}
+
+/// Call `func` with a path that refers to the open file.
+///
+/// Errors from [`path_err`] for the open file mention `display` instead.
+/// Other errors are returned unchanged.
+pub(crate) fn with_fd_path<D, T, F>(file: &D, display: &Path, func: F) -> io::Result<T>
+where
+    D: AsRawFd,
+    F: FnOnce(&Path) -> io::Result<T>,
+{
+    let open_path = fd_path(file);
+    func(&open_path).map_err(|err| match PathError::from_error(&err) {
+        Some(inner) if inner.path == open_path => {
+            let msg = io::Error::new(err.kind(), inner.msg.as_str());
+            path_err(display, &msg)
+        }
+        _ => err,
+    })
+}
+
+/// Return the path in `/proc/self/fd` for an open file.
+pub(crate) fn fd_path<D: AsRawFd>(fd: &D) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", fd.as_raw_fd()))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod at_tests {
+    use super::*;
+    use crate::from_mode;
+    use crate::nofollow::SymlinkError;
+    use std::fs::File;
+
+    #[test]
+    fn test_getfacl_at() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        fs::write(&file, "")?;
+        std::os::unix::fs::symlink("file", dir.path().join("link"))?;
+        let handle = File::open(dir.path())?;
+
+        setfacl_at(&handle, "file", &from_mode(0o640), None)?;
+        assert_eq!(getfacl_at(&handle, "file", None)?, from_mode(0o640));
+        assert_eq!(getfacl_at(&handle, ".", None)?, getfacl(dir.path(), None)?);
+
+        let err = getfacl_at(&handle, "link", None).unwrap_err();
+        let link = dir.path().join("link");
+        assert_eq!(SymlinkError::from_error(&err).unwrap().link, link);
+        assert!(setfacl_at(&handle, "link", &from_mode(0o600), None).is_err());
+        assert_eq!(getfacl(&file, None)?, from_mode(0o640));
+
+        for name in ["../file", "a/file", "", "/etc"] {
+            let err = getfacl_at(&handle, name, None).unwrap_err();
+            assert_eq!(err.to_string(), format!("invalid name: {name:?}"));
+        }
+
+        // Errors mention the directory's path.
+        let err = getfacl_at(&handle, "file", AclOption::DEFAULT_ACL).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("File {file:?}: Permission denied (os error 13)")
+        );
+        let err = getfacl_at(&handle, "missing", None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_acl_at_no_follow() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("file"), "")?;
+        std::os::unix::fs::symlink("file", dir.path().join("link"))?;
+        let handle = File::open(dir.path())?;
+
+        setfacl_at(&handle, "file", &from_mode(0o604), AclOption::NO_FOLLOW)?;
+        assert_eq!(
+            getfacl_at(&handle, "file", AclOption::NO_FOLLOW)?,
+            from_mode(0o604)
+        );
+        assert_eq!(
+            getfacl_at(&handle, ".", AclOption::NO_FOLLOW)?,
+            getfacl(dir.path(), None)?
+        );
+
+        let err = getfacl_at(&handle, "link", AclOption::NO_FOLLOW).unwrap_err();
+        assert!(SymlinkError::from_error(&err).is_some());
+        let err = setfacl_at(&handle, "link", &from_mode(0o600), AclOption::NO_FOLLOW);
+        assert!(SymlinkError::from_error(&err.unwrap_err()).is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_fd_path_errors() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let handle = File::open(dir.path())?;
+        let display = Path::new("display");
+
+        let err = with_fd_path(&handle, display, |path| -> io::Result<()> {
+            Err(path_err(path, &io::ErrorKind::NotFound.into()))
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(PathError::from_error(&err).unwrap().path, display);
+
+        // Errors with another payload can still be downcast.
+        let err = with_fd_path(&handle, display, |path| -> io::Result<()> {
+            Err(symlink_err(path, path))
+        })
+        .unwrap_err();
+        assert_eq!(
+            SymlinkError::from_error(&err).unwrap().link,
+            fd_path(&handle)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,507 @@
+//! Implements a scanner that flags risky ACL configurations.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::diff::is_default;
+use crate::failx::PathError;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+use crate::flag::Flag;
+use crate::getfacl;
+use crate::perm::Perm;
+use crate::policy::effective_perms;
+use crate::walk::Walk;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFREG: u32 = 0o100_000;
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+const S_ISVTX: u32 = 0o1000;
+
+/// Severity of an [`AuditFinding`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    /// Worth a look, but often intended.
+    Low,
+    /// Likely a mistake.
+    Medium,
+    /// Lets other users modify or take over the file.
+    High,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Kind of risky ACL configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FindingKind {
+    /// The `other` or `everyone` entry allows write or execute.
+    WorldAccess {
+        /// The offending entry.
+        entry: AclEntry,
+        /// The write and execute permissions it allows.
+        perms: Perm,
+    },
+
+    /// A named user or group has read, write or execute permissions that the
+    /// file owner does not have.
+    StrongerThanOwner {
+        /// The offending entry.
+        entry: AclEntry,
+        /// The permissions it allows beyond the owner's.
+        perms: Perm,
+    },
+
+    /// A directory's default ACL gives `other` or `everyone` write access to
+    /// new files.
+    DefaultWorldWrite(AclEntry),
+
+    /// A group can write to a setuid or setgid file.
+    SetidGroupWrite(AclEntry),
+
+    /// An NFSv4 deny entry follows an allow entry for the same principal, so
+    /// it has no effect on the permissions the allow entry grants.
+    ShadowedDeny {
+        /// The deny entry.
+        deny: AclEntry,
+        /// The earlier allow entry.
+        allow: AclEntry,
+        /// The denied permissions that are already allowed.
+        perms: Perm,
+    },
+
+    /// The file's ACL could not be read, so it was not audited.
+    Unreadable {
+        /// The error message.
+        error: String,
+    },
+}
+
+/// Risky ACL configuration found at a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct AuditFinding {
+    /// Path of the file.
+    pub path: PathBuf,
+
+    /// How serious the finding is.
+    pub severity: Severity,
+
+    /// What was found.
+    pub kind: FindingKind,
+}
+
+impl fmt::Display for AuditFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: ", self.path.display(), self.severity)?;
+        match &self.kind {
+            FindingKind::WorldAccess { entry, perms } => write!(f, "{entry} allows {perms}"),
+            FindingKind::StrongerThanOwner { entry, perms } => {
+                write!(f, "{entry} allows {perms} which the owner lacks")
+            }
+            FindingKind::DefaultWorldWrite(entry) => {
+                write!(f, "{entry} makes new files world-writable")
+            }
+            FindingKind::SetidGroupWrite(entry) => {
+                write!(f, "{entry} can write to a setuid/setgid file")
+            }
+            FindingKind::ShadowedDeny { deny, allow, perms } => {
+                write!(f, "{deny} is shadowed by {allow} for {perms}")
+            }
+            FindingKind::Unreadable { error } => write!(f, "cannot read ACL: {error}"),
+        }
+    }
+}
+
+/// Audit the ACL entries of a single file.
+///
+/// `mode` is the file's `st_mode`, including the file type bits. It
+/// determines whether the file is a directory, whether it is setuid or setgid,
+/// and what permissions the owner has.
+///
+/// On macOS, the mode bits are not part of the ACL, so only a group entry
+/// named `everyone` counts as world access.
+#[must_use]
+pub fn audit_entries(path: &Path, mode: u32, entries: &[AclEntry]) -> Vec<AuditFinding> {
+    let is_dir = mode & S_IFMT == S_IFDIR;
+    let is_setid = mode & S_IFMT == S_IFREG && mode & (S_ISUID | S_ISGID) != 0;
+    let owner = Perm::from_mode(mode)[0];
+    let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+
+    let mut result = Vec::new();
+    let mut push = |severity, kind| {
+        result.push(AuditFinding {
+            path: path.to_path_buf(),
+            severity,
+            kind,
+        });
+    };
+
+    for (i, entry) in entries.iter().enumerate() {
+        if !entry.allow {
+            if let Some((allow, perms)) = shadowing_allow(entry, &entries[..i]) {
+                push(
+                    Severity::Medium,
+                    FindingKind::ShadowedDeny {
+                        deny: entry.clone(),
+                        allow: allow.clone(),
+                        perms,
+                    },
+                );
+            }
+            continue;
+        }
+
+        let perms = effective_perms(entry, entries);
+
+        if is_dir && inherits_to_files(entry) && is_world(entry) && perms.contains(Perm::WRITE) {
+            push(
+                Severity::High,
+                FindingKind::DefaultWorldWrite(entry.clone()),
+            );
+        }
+
+        if !applies_to_file(entry) {
+            continue;
+        }
+
+        if is_world(entry) {
+            let mut world = perms & Perm::WRITE;
+            if !is_dir {
+                world |= perms & Perm::EXECUTE;
+            }
+            if !world.is_empty() {
+                let severity = if !world.contains(Perm::WRITE) {
+                    Severity::Low
+                } else if is_dir && mode & S_ISVTX != 0 {
+                    Severity::Medium
+                } else {
+                    Severity::High
+                };
+                push(
+                    severity,
+                    FindingKind::WorldAccess {
+                        entry: entry.clone(),
+                        perms: world,
+                    },
+                );
+            }
+        }
+
+        if is_named(entry) {
+            let extra = (perms & rwx) - owner;
+            if !extra.is_empty() {
+                push(
+                    Severity::Medium,
+                    FindingKind::StrongerThanOwner {
+                        entry: entry.clone(),
+                        perms: extra,
+                    },
+                );
+            }
+        }
+
+        if is_setid && entry.kind == AclEntryKind::Group && perms.contains(Perm::WRITE) {
+            push(Severity::High, FindingKind::SetidGroupWrite(entry.clone()));
+        }
+    }
+
+    result
+}
+
+/// Walk the tree at `root` and report every risky ACL configuration.
+///
+/// The findings for each file are listed in the order of its entries.
+/// Symlinks are not followed. A file whose ACL cannot be read is reported
+/// with [`FindingKind::Unreadable`], and the walk goes on.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::{audit, Severity};
+///
+/// for finding in audit("/srv")? {
+///     if finding.severity >= Severity::Medium {
+///         println!("{finding}");
+///     }
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the tree cannot be walked.
+pub fn audit<P: AsRef<Path>>(root: P) -> io::Result<Vec<AuditFinding>> {
+    let mut result = Vec::new();
+    for item in Walk::new(root.as_ref()) {
+        let entry = item?;
+        if entry.metadata.is_symlink() {
+            continue;
+        }
+
+        result.extend(audit_file(&entry.path, entry.metadata.mode()));
+    }
+    Ok(result)
+}
+
+/// Audit the ACL of a file, or report that it cannot be read.
+fn audit_file(path: &Path, mode: u32) -> Vec<AuditFinding> {
+    match getfacl(path, None) {
+        Ok(entries) => audit_entries(path, mode, &entries),
+        Err(err) => {
+            // The finding already names the path.
+            let error =
+                PathError::from_error(&err).map_or_else(|| err.to_string(), |err| err.msg.clone());
+            vec![AuditFinding {
+                path: path.to_path_buf(),
+                severity: Severity::Low,
+                kind: FindingKind::Unreadable { error },
+            }]
+        }
+    }
+}
+
+/// Return the first earlier allow entry for the same principal as `deny`
+/// that grants some of its permissions, and the permissions it grants.
+///
+/// An `everyone` allow entry shadows a deny entry for any principal.
+fn shadowing_allow<'a>(deny: &AclEntry, before: &'a [AclEntry]) -> Option<(&'a AclEntry, Perm)> {
+    if !applies_to_file(deny) {
+        return None;
+    }
+
+    before
+        .iter()
+        .filter(|allow| allow.allow && applies_to_file(allow))
+        .filter(|allow| (allow.kind == deny.kind && allow.name == deny.name) || is_everyone(allow))
+        .find_map(|allow| {
+            let perms = allow.perms & deny.perms;
+            (!perms.is_empty()).then_some((allow, perms))
+        })
+}
+
+/// Return true if the entry applies to every user.
+fn is_world(entry: &AclEntry) -> bool {
+    match entry.kind {
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        AclEntryKind::Other => true,
+        _ => is_everyone(entry),
+    }
+}
+
+/// Return true if the entry is an NFSv4 `everyone@` entry.
+#[cfg(target_os = "freebsd")]
+fn is_everyone(entry: &AclEntry) -> bool {
+    entry.kind == AclEntryKind::Everyone
+}
+
+/// Return true if the entry is an NFSv4 `everyone@` entry.
+#[cfg(target_os = "macos")]
+fn is_everyone(entry: &AclEntry) -> bool {
+    entry.kind == AclEntryKind::Group && entry.name == "everyone"
+}
+
+/// Return true if the entry is an NFSv4 `everyone@` entry.
+#[cfg(target_os = "linux")]
+const fn is_everyone(_entry: &AclEntry) -> bool {
+    false
+}
+
+/// Return true if the entry is for a named user or group.
+fn is_named(entry: &AclEntry) -> bool {
+    matches!(entry.kind, AclEntryKind::User | AclEntryKind::Group)
+        && !entry.name.is_empty()
+        && !is_everyone(entry)
+}
+
+/// Return true if the entry is inherited by new files in a directory.
+#[cfg(target_os = "linux")]
+fn inherits_to_files(entry: &AclEntry) -> bool {
+    is_default(entry)
+}
+
+/// Return true if the entry is inherited by new files in a directory.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn inherits_to_files(entry: &AclEntry) -> bool {
+    is_default(entry) || entry.flags.contains(Flag::FILE_INHERIT)
+}
+
+/// Return true if the entry applies to the file itself.
+#[cfg(target_os = "linux")]
+fn applies_to_file(entry: &AclEntry) -> bool {
+    !is_default(entry)
+}
+
+/// Return true if the entry applies to the file itself.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn applies_to_file(entry: &AclEntry) -> bool {
+    !is_default(entry) && !entry.flags.contains(Flag::ONLY_INHERIT)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, target_os = "linux"))]
+mod audit_tests {
+    use super::*;
+    use crate::{from_mode, setfacl, Flag};
+    use std::fs;
+
+    fn kinds(findings: &[AuditFinding]) -> Vec<(Severity, &FindingKind)> {
+        findings
+            .iter()
+            .map(|finding| (finding.severity, &finding.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_audit_world_access() {
+        let path = Path::new("f");
+        assert!(audit_entries(path, S_IFREG | 0o644, &from_mode(0o644)).is_empty());
+        assert!(audit_entries(path, S_IFDIR | 0o755, &from_mode(0o755)).is_empty());
+
+        let entries = from_mode(0o757);
+        let findings = audit_entries(path, S_IFREG | 0o757, &entries);
+        assert_eq!(
+            kinds(&findings),
+            vec![(
+                Severity::High,
+                &FindingKind::WorldAccess {
+                    entry: entries[2].clone(),
+                    perms: Perm::WRITE | Perm::EXECUTE
+                }
+            )]
+        );
+
+        let findings = audit_entries(path, S_IFREG | 0o755, &from_mode(0o755));
+        assert_eq!(findings[0].severity, Severity::Low);
+
+        // Sticky directories, such as /tmp.
+        let findings = audit_entries(path, S_IFDIR | 0o1777, &from_mode(0o777));
+        assert_eq!(findings[0].severity, Severity::Medium);
+        assert_eq!(
+            findings[0].to_string(),
+            "f: medium: allow::other::read,write,execute allows write"
+        );
+    }
+
+    #[test]
+    fn test_audit_named_and_default() {
+        let path = Path::new("d");
+        let mut entries = from_mode(0o750);
+        entries.push(AclEntry::allow_user("500", Perm::READ, None));
+        entries.push(AclEntry::allow_group("501", Perm::WRITE, None));
+        entries.push(AclEntry::allow_mask(Perm::READ | Perm::WRITE, None));
+        entries.push(AclEntry::allow_other(Perm::WRITE, Flag::DEFAULT));
+        let findings = audit_entries(path, S_IFDIR | 0o570, &entries);
+
+        assert_eq!(
+            kinds(&findings),
+            vec![
+                (
+                    Severity::Medium,
+                    &FindingKind::StrongerThanOwner {
+                        entry: entries[4].clone(),
+                        perms: Perm::WRITE
+                    }
+                ),
+                (
+                    Severity::High,
+                    &FindingKind::DefaultWorldWrite(entries[6].clone())
+                ),
+            ]
+        );
+
+        // The mask limits the named entry.
+        entries[5] = AclEntry::allow_mask(Perm::READ, None);
+        let findings = audit_entries(path, S_IFDIR | 0o570, &entries);
+        assert_eq!(findings.len(), 1);
+    }
+
+    #[test]
+    fn test_audit_setid() {
+        let path = Path::new("bin");
+        let entries = from_mode(0o4775);
+        let findings = audit_entries(path, S_IFREG | 0o4775, &entries);
+        assert_eq!(
+            kinds(&findings),
+            vec![
+                (
+                    Severity::High,
+                    &FindingKind::SetidGroupWrite(entries[1].clone())
+                ),
+                (
+                    Severity::Low,
+                    &FindingKind::WorldAccess {
+                        entry: entries[2].clone(),
+                        perms: Perm::EXECUTE
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_audit() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        fs::write(&file, "data")?;
+        setfacl(&[&dir], &from_mode(0o700), None)?;
+        setfacl(&[&file], &from_mode(0o646), None)?;
+
+        let findings = audit(&dir)?;
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].path, file);
+        assert_eq!(findings[0].severity, Severity::High);
+
+        // A file that cannot be read is a finding, not an error.
+        let missing = dir.path().join("missing");
+        let findings = audit_file(&missing, S_IFREG | 0o644);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Low);
+        assert_eq!(
+            findings[0].to_string(),
+            format!(
+                "{}: low: cannot read ACL: No such file or directory (os error 2)",
+                missing.display()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_audit_serde() {
+        let finding = AuditFinding {
+            path: PathBuf::from("f"),
+            severity: Severity::High,
+            kind: FindingKind::SetidGroupWrite(AclEntry::allow_group("", Perm::WRITE, None)),
+        };
+        let json = serde_json::to_string(&finding).unwrap();
+        assert!(json.contains(r#""severity":"high","kind":{"setid_group_write":"#));
+        assert_eq!(
+            serde_json::from_str::<AuditFinding>(&json).unwrap(),
+            finding
+        );
+    }
+}
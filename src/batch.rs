@@ -0,0 +1,116 @@
+//! Implements `getfacl` for many paths on a pool of threads.
+
+use crate::_getfacl;
+use crate::acl::AclOption;
+use crate::aclentry::AclEntry;
+use crate::names::NameCache;
+
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Get the access control list (ACL) for many files and directories.
+///
+/// The paths are read by a pool of `threads` threads. If `threads` is 0, the
+/// pool has one thread per available CPU. The threads share a cache of user
+/// and group names, so each owner is looked up once per batch rather than
+/// once per file.
+///
+/// Returns one result for each path, in the same order as `paths`. An error
+/// for one path does not stop the batch.
+///
+/// # Example
+///
+/// ```no_run
+/// use exacl::getfacl_batch;
+///
+/// for (path, result) in getfacl_batch(&["./tmp/foo", "./tmp/bar"], None, 8) {
+///     match result {
+///         Ok(entries) => println!("{}: {} entries", path.display(), entries.len()),
+///         Err(err) => eprintln!("{err}"),
+///     }
+/// }
+/// ```
+pub fn getfacl_batch<P, O>(
+    paths: &[P],
+    options: O,
+    threads: usize,
+) -> Vec<(PathBuf, io::Result<Vec<AclEntry>>)>
+where
+    P: AsRef<Path> + Sync,
+    O: Into<Option<AclOption>>,
+{
+    let options = options.into().unwrap_or_default();
+    let threads = if threads == 0 {
+        thread::available_parallelism().map_or(1, NonZeroUsize::get)
+    } else {
+        threads
+    };
+
+    let names = NameCache::default();
+    let next = AtomicUsize::new(0);
+    let worker = || {
+        let mut results = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(path) = paths.get(i) else {
+                break;
+            };
+            results.push((i, _getfacl(path.as_ref(), options, Some(&names))));
+        }
+        results
+    };
+
+    let mut results = thread::scope(|scope| {
+        let workers = (0..threads.min(paths.len()))
+            .map(|_| scope.spawn(worker))
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("getfacl_batch worker panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    results.sort_unstable_by_key(|(i, _)| *i);
+    results
+        .into_iter()
+        .map(|(i, result)| (paths[i].as_ref().to_path_buf(), result))
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod batch_tests {
+    use super::*;
+    use crate::getfacl;
+
+    #[test]
+    fn test_getfacl_batch() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut paths = Vec::new();
+        for i in 0..20 {
+            let path = dir.path().join(format!("file{i}"));
+            std::fs::write(&path, "")?;
+            paths.push(path);
+        }
+        paths.insert(5, dir.path().join("missing"));
+
+        let results = getfacl_batch(&paths, None, 4);
+        assert_eq!(results.len(), paths.len());
+        for ((path, result), expected) in results.iter().zip(&paths) {
+            assert_eq!(path, expected);
+            if path.ends_with("missing") {
+                assert!(result.is_err());
+            } else {
+                assert_eq!(result.as_ref().unwrap(), &getfacl(path, None)?);
+            }
+        }
+
+        assert!(getfacl_batch::<&Path, _>(&[], None, 0).is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+//! Implements `AclBuilder` for constructing POSIX.1e ACL's.
+
+use crate::aclentry::AclEntry;
+use crate::failx::fail_custom;
+use crate::flag::Flag;
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+
+use std::io;
+
+/// Builder for a complete POSIX.1e access control list.
+///
+/// The builder starts from the traditional mode bits, which provide the
+/// owner, group owner and other entries. Named users and groups are added
+/// with [`user`](AclBuilder::user) and [`group`](AclBuilder::group). When
+/// the ACL has named entries, [`build`](AclBuilder::build) adds a mask entry
+/// that grants the union of the group class permissions.
+///
+/// Each name is looked up when it is added. The first error is kept and
+/// returned by `build`.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::{setfacl, AclBuilder, Perm};
+///
+/// let entries = AclBuilder::from_mode(0o750)
+///     .user("some_user", Perm::READ | Perm::WRITE | Perm::EXECUTE)
+///     .group("some_group", Perm::READ | Perm::EXECUTE)
+///     .with_defaults()
+///     .build()?;
+///
+/// setfacl(&["./tmp/dir"], &entries, None)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct AclBuilder {
+    owner: Perm,
+    group: Perm,
+    other: Perm,
+    users: Vec<(String, Perm)>,
+    groups: Vec<(String, Perm)>,
+    added: Vec<Qualifier>,
+    defaults: bool,
+    error: Option<String>,
+}
+
+impl AclBuilder {
+    /// Start an ACL from the owner/group/other permission bits in `mode`.
+    ///
+    /// Extra bits outside the mask 0o777 are ignored.
+    pub fn from_mode(mode: u32) -> AclBuilder {
+        let [owner, group, other] = Perm::from_mode(mode);
+        AclBuilder {
+            owner,
+            group,
+            other,
+            users: Vec::new(),
+            groups: Vec::new(),
+            added: Vec::new(),
+            defaults: false,
+            error: None,
+        }
+    }
+
+    /// Add an entry for a named user.
+    ///
+    /// `name` is a user name or a decimal user ID. Use
+    /// [`from_mode`](AclBuilder::from_mode) for the file owner.
+    pub fn user(mut self, name: &str, perms: Perm) -> AclBuilder {
+        match check_name("user", name, &self.added, Qualifier::user_named) {
+            Ok(qualifier) => self.added.push(qualifier),
+            Err(err) => self.set_error(err),
+        }
+        self.users.push((name.to_string(), perms));
+        self
+    }
+
+    /// Add an entry for a named group.
+    ///
+    /// `name` is a group name or a decimal group ID. Use
+    /// [`from_mode`](AclBuilder::from_mode) for the group owner.
+    pub fn group(mut self, name: &str, perms: Perm) -> AclBuilder {
+        match check_name("group", name, &self.added, Qualifier::group_named) {
+            Ok(qualifier) => self.added.push(qualifier),
+            Err(err) => self.set_error(err),
+        }
+        self.groups.push((name.to_string(), perms));
+        self
+    }
+
+    /// Also include a copy of every entry in the default ACL.
+    ///
+    /// The default ACL is inherited by new files and subdirectories, so
+    /// only use this for a directory.
+    pub fn with_defaults(mut self) -> AclBuilder {
+        self.defaults = true;
+        self
+    }
+
+    /// Return the ACL entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if a name is unknown, or if a user or group is
+    /// added twice, even under a different name or as a decimal ID.
+    pub fn build(self) -> io::Result<Vec<AclEntry>> {
+        if let Some(msg) = &self.error {
+            return fail_custom(msg);
+        }
+
+        let mut entries = self.entries(None);
+        if self.defaults {
+            entries.extend(self.entries(Flag::DEFAULT));
+        }
+        Ok(entries)
+    }
+
+    /// Return the entries of one ACL with the given flags.
+    fn entries(&self, flags: impl Into<Option<Flag>> + Copy) -> Vec<AclEntry> {
+        let mut entries = Vec::with_capacity(4 + self.users.len() + self.groups.len());
+        entries.push(AclEntry::allow_user("", self.owner, flags));
+        for (name, perms) in &self.users {
+            entries.push(AclEntry::allow_user(name, *perms, flags));
+        }
+        entries.push(AclEntry::allow_group("", self.group, flags));
+        for (name, perms) in &self.groups {
+            entries.push(AclEntry::allow_group(name, *perms, flags));
+        }
+
+        if !self.users.is_empty() || !self.groups.is_empty() {
+            let mask = self
+                .users
+                .iter()
+                .chain(&self.groups)
+                .fold(self.group, |mask, (_, perms)| mask | *perms);
+            entries.push(AclEntry::allow_mask(mask, flags));
+        }
+
+        entries.push(AclEntry::allow_other(self.other, flags));
+        entries
+    }
+
+    /// Keep the first error.
+    fn set_error(&mut self, msg: String) {
+        self.error.get_or_insert(msg);
+    }
+}
+
+/// Check that a name refers to a principal that has not been added already,
+/// and return its qualifier.
+///
+/// Names are compared by uid or gid, so "root" and "0" are the same.
+fn check_name<F>(
+    kind: &str,
+    name: &str,
+    added: &[Qualifier],
+    lookup: F,
+) -> Result<Qualifier, String>
+where
+    F: FnOnce(&str) -> io::Result<Qualifier>,
+{
+    if name.is_empty() {
+        return Err(format!("{kind} name is empty; use from_mode for the owner"));
+    }
+    let qualifier = lookup(name).map_err(|err| err.to_string())?;
+    if added.contains(&qualifier) {
+        return Err(format!("duplicate entry for {kind} {name:?}"));
+    }
+    Ok(qualifier)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod builder_tests {
+    use super::*;
+    use crate::{from_mode, to_string};
+
+    #[test]
+    fn test_builder() -> io::Result<()> {
+        assert_eq!(AclBuilder::from_mode(0o750).build()?, from_mode(0o750));
+
+        let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+        let entries = AclBuilder::from_mode(0o740)
+            .user("500", rwx)
+            .group("501", Perm::READ | Perm::EXECUTE)
+            .with_defaults()
+            .build()?;
+        assert_eq!(
+            to_string(&entries)?,
+            "allow::user::read,write,execute\n\
+             allow::user:500:read,write,execute\n\
+             allow::group::read\n\
+             allow::group:501:read,execute\n\
+             allow::mask::read,write,execute\n\
+             allow::other::\n\
+             allow:default:user::read,write,execute\n\
+             allow:default:user:500:read,write,execute\n\
+             allow:default:group::read\n\
+             allow:default:group:501:read,execute\n\
+             allow:default:mask::read,write,execute\n\
+             allow:default:other::\n"
+        );
+
+        let dir = tempfile::tempdir()?;
+        crate::setfacl(&[&dir], &entries, None)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        let err = AclBuilder::from_mode(0o750)
+            .user("", Perm::READ)
+            .user("non_existent_user_5f2a", Perm::READ)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "user name is empty; use from_mode for the owner"
+        );
+
+        let err = AclBuilder::from_mode(0o750)
+            .group("500", Perm::READ)
+            .group("500", Perm::WRITE)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "duplicate entry for group \"500\"");
+
+        let err = AclBuilder::from_mode(0o750)
+            .user("root", Perm::READ)
+            .group("0", Perm::READ)
+            .user("0", Perm::WRITE)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "duplicate entry for user \"0\"");
+
+        let err = AclBuilder::from_mode(0o750)
+            .user("non_existent_user_5f2a", Perm::READ)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown user name: \"non_existent_user_5f2a\""
+        );
+    }
+}
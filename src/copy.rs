@@ -0,0 +1,332 @@
+//! Implements file and tree copies that preserve ACL's.
+
+use crate::aclentry::AclEntry;
+use crate::failx::path_err;
+use crate::support::{acl_support, AclSupport};
+use crate::walk::Walk;
+use crate::{getfacl, setfacl};
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Result of [`copy_with_acl`] and [`copy_tree_with_acl`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CopyReport {
+    /// Number of files and directories copied.
+    pub copied: usize,
+
+    /// Destination paths whose ACL could not be set, because the destination
+    /// file system does not support it. Each path has the mode bits of the
+    /// source, and lists the source entries that the mode bits do not cover.
+    /// A path is only listed if some entry was lost.
+    pub approximated: Vec<(PathBuf, Vec<AclEntry>)>,
+
+    /// Source paths that were not copied, because they are not a regular
+    /// file, directory or symlink.
+    pub skipped: Vec<PathBuf>,
+}
+
+impl CopyReport {
+    /// Return true if every ACL was copied exactly and nothing was skipped.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.approximated.is_empty() && self.skipped.is_empty()
+    }
+}
+
+/// Copy a file, including its ACL.
+///
+/// The contents and mode bits are copied first. Then the access ACL of
+/// `src`, and its default ACL on Linux and FreeBSD, are set on `dst`.
+///
+/// `dst` must not exist; an existing file is not overwritten, and a symlink
+/// at `dst` is not followed.
+///
+/// If the file system of `dst` does not support ACL's, or does not support
+/// the kind of ACL that `src` has, `dst` keeps the mode bits of `src`. The
+/// entries that the mode bits cannot express, such as named users and
+/// groups, are listed in [`CopyReport::approximated`].
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let report = exacl::copy_with_acl("./staging/app", "./production/app")?;
+/// for (path, lost) in &report.approximated {
+///     eprintln!("{}: {} entries not preserved", path.display(), lost.len());
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn copy_with_acl<P, Q>(src: P, dst: Q) -> io::Result<CopyReport>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    let mut report = CopyReport::default();
+
+    copy_file(src, dst)?;
+    copy_acl(src, dst, &mut report)?;
+    report.copied += 1;
+
+    Ok(report)
+}
+
+/// Copy a directory tree, including the ACL of every file and directory.
+///
+/// `dst` must not exist. Files are copied as in [`copy_with_acl`].
+/// Directories are created with the mode bits and ACL of their source; the
+/// ACL of a directory is set after its contents are copied, so a restrictive
+/// ACL does not get in the way of the copy. Symlinks are recreated, not
+/// followed. Other special files, such as sockets and FIFOs, are skipped.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. The copy stops at the first error;
+/// files before it have already been copied.
+pub fn copy_tree_with_acl<P, Q>(src: P, dst: Q) -> io::Result<CopyReport>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let dst = dst.as_ref();
+    let mut report = CopyReport::default();
+    let mut dirs = Vec::new();
+
+    for item in Walk::new(src.as_ref()) {
+        let entry = item?;
+        let target = if entry.relative.as_os_str().is_empty() {
+            dst.to_path_buf()
+        } else {
+            dst.join(&entry.relative)
+        };
+        let file_type = entry.metadata.file_type();
+
+        if file_type.is_dir() {
+            fs::create_dir(&target).map_err(|err| path_err(&target, &err))?;
+            dirs.push((entry.path, target, entry.metadata.permissions()));
+        } else if file_type.is_file() {
+            copy_file(&entry.path, &target)?;
+            copy_acl(&entry.path, &target, &mut report)?;
+            report.copied += 1;
+        } else if file_type.is_symlink() {
+            let link = fs::read_link(&entry.path).map_err(|err| path_err(&entry.path, &err))?;
+            std::os::unix::fs::symlink(link, &target).map_err(|err| path_err(&target, &err))?;
+        } else {
+            report.skipped.push(entry.path);
+        }
+    }
+
+    for (path, target, permissions) in dirs.into_iter().rev() {
+        fs::set_permissions(&target, permissions).map_err(|err| path_err(&target, &err))?;
+        copy_acl(&path, &target, &mut report)?;
+        report.copied += 1;
+    }
+
+    Ok(report)
+}
+
+/// Copy the contents and mode bits of a file.
+///
+/// Unlike [`fs::copy`], errors mention the path that failed. An error while
+/// copying the contents mentions `dst`. `dst` is created, and must not exist.
+fn copy_file(src: &Path, dst: &Path) -> io::Result<()> {
+    let mut reader = fs::File::open(src).map_err(|err| path_err(src, &err))?;
+    let permissions = reader
+        .metadata()
+        .map_err(|err| path_err(src, &err))?
+        .permissions();
+    let mut writer = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)
+        .map_err(|err| path_err(dst, &err))?;
+    io::copy(&mut reader, &mut writer).map_err(|err| path_err(dst, &err))?;
+    writer
+        .set_permissions(permissions)
+        .map_err(|err| path_err(dst, &err))
+}
+
+/// Set the ACL of `src` on `dst`, which already has the mode bits of `src`.
+fn copy_acl(src: &Path, dst: &Path, report: &mut CopyReport) -> io::Result<()> {
+    let entries = match getfacl(src, None) {
+        Ok(entries) => entries,
+        // The source has no ACL beyond its mode bits.
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    // Setting a POSIX.1e ACL on a file system with NFSv4 ACL's, or the other
+    // way around, fails with EINVAL on FreeBSD.
+    let result = if acl_support(dst)? != acl_kind(&entries) {
+        Err(io::ErrorKind::Unsupported.into())
+    } else {
+        setfacl(&[dst], &entries, None)
+    };
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+            let lost = entries
+                .into_iter()
+                .filter(|entry| !is_mode_entry(entry))
+                .collect::<Vec<_>>();
+            if !lost.is_empty() {
+                report.approximated.push((dst.to_path_buf(), lost));
+            }
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Return the kind of ACL that `entries` were read from.
+///
+/// A POSIX.1e access ACL always has an "other" entry; an `NFSv4` ACL never
+/// does.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn acl_kind(entries: &[AclEntry]) -> AclSupport {
+    use crate::aclentry::AclEntryKind;
+
+    if entries
+        .iter()
+        .any(|entry| entry.kind == AclEntryKind::Other)
+    {
+        AclSupport::Posix
+    } else {
+        AclSupport::Nfs4
+    }
+}
+
+/// Return the kind of ACL that `entries` were read from.
+///
+/// macOS extended ACL's are reported as [`AclSupport::Nfs4`], as in
+/// [`acl_support`].
+#[cfg(target_os = "macos")]
+const fn acl_kind(_entries: &[AclEntry]) -> AclSupport {
+    AclSupport::Nfs4
+}
+
+/// Return true if an entry is expressed by the mode bits.
+///
+/// These are the unnamed owner, group, mask and other entries of the access
+/// ACL. macOS entries always have a name.
+fn is_mode_entry(entry: &AclEntry) -> bool {
+    entry.name.is_empty() && entry.allow && entry.flags.is_empty()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, target_os = "linux"))]
+mod copy_tests {
+    use super::*;
+    use crate::{from_mode, AclEntry, Flag, Perm};
+
+    #[test]
+    fn test_copy_with_acl() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        fs::write(&src, "data")?;
+
+        let mut entries = from_mode(0o640);
+        entries.push(AclEntry::allow_user("500", Perm::READ, None));
+        entries.push(AclEntry::allow_mask(Perm::READ, None));
+        setfacl(&[&src], &entries, None)?;
+
+        let report = copy_with_acl(&src, &dst)?;
+        assert_eq!(report.copied, 1);
+        assert!(report.is_complete());
+        assert_eq!(fs::read(&dst)?, b"data");
+        assert_eq!(getfacl(&dst, None)?, getfacl(&src, None)?);
+
+        let missing = dir.path().join("missing");
+        let err = copy_with_acl(&missing, &dst).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("File {missing:?}: ")));
+
+        // An existing file, or a symlink, at the destination is not replaced.
+        let link = dir.path().join("link");
+        let target = dir.path().join("target");
+        std::os::unix::fs::symlink(&target, &link)?;
+        let err = copy_with_acl(&src, &link).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(!target.exists());
+        let err = copy_with_acl(&src, &dst).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        // Errors on the destination side mention the destination.
+        let bad_dst = missing.join("dst");
+        let err = copy_with_acl(&src, &bad_dst).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("File {bad_dst:?}: ")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_tree_with_acl() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        fs::create_dir(&src)?;
+        fs::create_dir(src.join("sub"))?;
+        fs::write(src.join("sub/file"), "data")?;
+        std::os::unix::fs::symlink("sub/file", src.join("link"))?;
+
+        let mut entries = from_mode(0o750);
+        entries.push(AclEntry::allow_user("500", Perm::READ, Flag::DEFAULT));
+        entries.extend(from_mode(0o750).into_iter().map(|entry| AclEntry {
+            flags: Flag::DEFAULT,
+            ..entry
+        }));
+        entries.push(AclEntry::allow_mask(Perm::READ, Flag::DEFAULT));
+        setfacl(&[&src, &src.join("sub")], &entries, None)?;
+        setfacl(&[&src.join("sub/file")], &from_mode(0o600), None)?;
+
+        let report = copy_tree_with_acl(&src, &dst)?;
+        assert_eq!(report.copied, 3);
+        assert!(report.is_complete());
+        for path in ["", "sub", "sub/file"] {
+            assert_eq!(
+                getfacl(dst.join(path), None)?,
+                getfacl(src.join(path), None)?
+            );
+        }
+        assert_eq!(fs::read_link(dst.join("link"))?, Path::new("sub/file"));
+
+        // The destination must not exist.
+        assert!(copy_tree_with_acl(&src, &dst).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_acl_kind() {
+        assert_eq!(acl_kind(&from_mode(0o640)), AclSupport::Posix);
+        assert_eq!(
+            acl_kind(&[AclEntry::allow_user("500", Perm::READ, None)]),
+            AclSupport::Nfs4
+        );
+    }
+
+    #[test]
+    fn test_is_mode_entry() {
+        assert!(is_mode_entry(&AclEntry::allow_user("", Perm::READ, None)));
+        assert!(!is_mode_entry(&AclEntry::allow_user(
+            "500",
+            Perm::READ,
+            None
+        )));
+        assert!(!is_mode_entry(&AclEntry::allow_other(
+            Perm::READ,
+            Flag::DEFAULT
+        )));
+    }
+}
@@ -0,0 +1,262 @@
+//! Implements a structured diff between two lists of ACL entries.
+
+use crate::aclentry::AclEntry;
+#[cfg(not(target_os = "macos"))]
+use crate::flag::Flag;
+use crate::perm::Perm;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Entry whose permissions or flags changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct AclEntryChange {
+    /// Entry before the change.
+    pub old: AclEntry,
+
+    /// Entry after the change.
+    pub new: AclEntry,
+}
+
+impl AclEntryChange {
+    /// Return the permissions that were added.
+    #[must_use]
+    pub fn added_perms(&self) -> Perm {
+        self.new.perms - self.old.perms
+    }
+
+    /// Return the permissions that were removed.
+    #[must_use]
+    pub fn removed_perms(&self) -> Perm {
+        self.old.perms - self.new.perms
+    }
+}
+
+/// Difference between two lists of ACL entries.
+///
+/// Entries are matched by kind, name, allow/deny and whether they belong to
+/// the default ACL. An entry that only exists in the new list is added; an
+/// entry that only exists in the old list is removed. A matched entry whose
+/// permissions or flags differ is changed.
+///
+/// Each list is sorted in the canonical order defined by `Ord` for
+/// [`AclEntry`].
+///
+/// # Text Format
+///
+/// `AclDiff` is displayed with one line per difference, in canonical order.
+/// Removed entries are prefixed by `-`, added entries by `+` and changed
+/// entries by `~`. A changed entry shows the old entry and the new
+/// permissions, or the whole new entry if the flags changed.
+///
+/// ```text
+/// ~ allow::user:chip:read -> read,write
+/// - allow::group:admin:read,write
+/// + allow:default:user:chet:read
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct AclDiff {
+    /// Entries that only exist in the new list.
+    pub added: Vec<AclEntry>,
+
+    /// Entries that only exist in the old list.
+    pub removed: Vec<AclEntry>,
+
+    /// Entries that exist in both lists with different permissions or flags.
+    pub changed: Vec<AclEntryChange>,
+}
+
+impl AclDiff {
+    /// Return true if there are no differences.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare two lists of ACL entries.
+///
+/// The order of entries within each list does not matter.
+///
+/// # Example
+///
+/// ```
+/// use exacl::{diff, AclEntry, Perm};
+///
+/// let old = vec![AclEntry::allow_user("chip", Perm::READ, None)];
+/// let new = vec![AclEntry::allow_user("chip", Perm::READ | Perm::WRITE, None)];
+///
+/// let changes = diff(&old, &new);
+/// assert_eq!(changes.changed[0].added_perms(), Perm::WRITE);
+/// assert_eq!(changes.to_string(), "~ allow::user:chip:read -> read,write\n");
+/// ```
+#[must_use]
+pub fn diff(old: &[AclEntry], new: &[AclEntry]) -> AclDiff {
+    let mut old_sorted = old.iter().collect::<Vec<_>>();
+    let mut new_sorted = new.iter().collect::<Vec<_>>();
+    old_sorted.sort_by(|a, b| compare_keys(a, b));
+    new_sorted.sort_by(|a, b| compare_keys(a, b));
+
+    // Merge the two sorted lists. Entries with equal keys are paired in
+    // their original order.
+    let mut result = AclDiff::default();
+    let mut old_iter = old_sorted.into_iter().peekable();
+    let mut new_iter = new_sorted.into_iter().peekable();
+
+    loop {
+        let order = match (old_iter.peek(), new_iter.peek()) {
+            (Some(a), Some(b)) => compare_keys(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+
+        match order {
+            Ordering::Less => result.removed.extend(old_iter.next().cloned()),
+            Ordering::Greater => result.added.extend(new_iter.next().cloned()),
+            Ordering::Equal => {
+                let a = old_iter.next().expect("old entry");
+                let b = new_iter.next().expect("new entry");
+                if a != b {
+                    result.changed.push(AclEntryChange {
+                        old: a.clone(),
+                        new: b.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    result.added.sort();
+    result.removed.sort();
+    result.changed.sort_by(|a, b| a.old.cmp(&b.old));
+    result
+}
+
+/// Return true if the entry belongs to the default ACL.
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn is_default(entry: &AclEntry) -> bool {
+    entry.flags.contains(Flag::DEFAULT)
+}
+
+/// Return true if the entry belongs to the default ACL.
+#[cfg(target_os = "macos")]
+pub(crate) const fn is_default(_entry: &AclEntry) -> bool {
+    false
+}
+
+/// Compare the keys used to match entries.
+pub(crate) fn compare_keys(a: &AclEntry, b: &AclEntry) -> Ordering {
+    is_default(a)
+        .cmp(&is_default(b))
+        .then_with(|| a.allow.cmp(&b.allow))
+        .then_with(|| a.kind.cmp(&b.kind))
+        .then_with(|| a.name.cmp(&b.name))
+}
+
+impl fmt::Display for AclDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines =
+            Vec::with_capacity(self.added.len() + self.removed.len() + self.changed.len());
+        lines.extend(
+            self.removed
+                .iter()
+                .map(|entry| (entry, format!("- {entry}"))),
+        );
+        lines.extend(self.added.iter().map(|entry| (entry, format!("+ {entry}"))));
+        lines.extend(self.changed.iter().map(|change| {
+            let line = if change.old.flags == change.new.flags {
+                format!("~ {} -> {}", change.old, change.new.perms)
+            } else {
+                format!("~ {} -> {}", change.old, change.new)
+            };
+            (&change.old, line)
+        }));
+        lines.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (_, line) in lines {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod diff_tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_empty() {
+        let entries = vec![
+            AclEntry::allow_user("a", Perm::READ, None),
+            AclEntry::allow_group("b", Perm::WRITE, None),
+        ];
+        let reversed = entries.iter().rev().cloned().collect::<Vec<_>>();
+
+        let changes = diff(&entries, &reversed);
+        assert!(changes.is_empty());
+        assert_eq!(changes.to_string(), "");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "macos"))]
+    fn test_diff() {
+        let old = vec![
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_user("chip", Perm::READ, None),
+            AclEntry::allow_group("admin", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_user("chip", Perm::READ, Flag::DEFAULT),
+        ];
+        let new = vec![
+            AclEntry::allow_user("chip", Perm::READ, Flag::DEFAULT),
+            AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_user("chip", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_user("chet", Perm::READ, Flag::DEFAULT),
+        ];
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes.added,
+            [AclEntry::allow_user("chet", Perm::READ, Flag::DEFAULT)]
+        );
+        assert_eq!(
+            changes.removed,
+            [AclEntry::allow_group(
+                "admin",
+                Perm::READ | Perm::WRITE,
+                None
+            )]
+        );
+        assert_eq!(changes.changed.len(), 1);
+        assert_eq!(changes.changed[0].added_perms(), Perm::WRITE);
+        assert_eq!(changes.changed[0].removed_perms(), Perm::empty());
+
+        assert_eq!(
+            changes.to_string(),
+            "~ allow::user:chip:read -> read,write\n- allow::group:admin:read,write\n+ allow:default:user:chet:read\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_diff_serde() {
+        let old = vec![AclEntry::allow_user("a", Perm::READ, None)];
+        let new = vec![AclEntry::allow_group("b", Perm::WRITE, None)];
+
+        let changes = diff(&old, &new);
+        let json = serde_json::to_string(&changes).unwrap();
+        assert_eq!(
+            json,
+            r#"{"added":[{"kind":"group","name":"b","perms":["write"],"flags":[],"allow":true}],"removed":[{"kind":"user","name":"a","perms":["read"],"flags":[],"allow":true}],"changed":[]}"#
+        );
+        assert_eq!(serde_json::from_str::<AclDiff>(&json).unwrap(), changes);
+    }
+}
@@ -3,9 +3,10 @@
 #![allow(dead_code)]
 
 use log::debug;
+use std::error::Error;
 use std::fmt;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Log a message and return an [`io::Error`] with the value of errno.
 pub fn log_err<R, T>(ret: R, func: &str, arg: T) -> io::Error
@@ -48,7 +49,7 @@ where
 
 /// Return a custom [`io::Result`] with the given message.
 pub fn fail_custom<U>(msg: &str) -> io::Result<U> {
-    Err(io::Error::new(io::ErrorKind::Other, msg))
+    Err(io::Error::other(msg))
 }
 
 /// Return a custom [`io::Error`] that prefixes the given error.
@@ -58,5 +59,36 @@ pub fn custom_err(msg: &str, err: &io::Error) -> io::Error {
 
 /// Return a custom [`io::Error`] that prefixes the given error with filename.
 pub fn path_err(path: &Path, err: &io::Error) -> io::Error {
-    io::Error::new(err.kind(), format!("File {path:?}: {err}"))
+    io::Error::new(
+        err.kind(),
+        PathError {
+            path: path.to_path_buf(),
+            msg: err.to_string(),
+        },
+    )
 }
+
+/// Error message prefixed with a filename, built by [`path_err`].
+#[derive(Debug)]
+pub struct PathError {
+    /// Path of the file.
+    pub path: PathBuf,
+
+    /// Message of the original error.
+    pub msg: String,
+}
+
+impl PathError {
+    /// Return the path error wrapped by `err`.
+    pub fn from_error(err: &io::Error) -> Option<&PathError> {
+        err.get_ref()?.downcast_ref::<PathError>()
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "File {:?}: {}", self.path, self.msg)
+    }
+}
+
+impl Error for PathError {}
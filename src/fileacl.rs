@@ -0,0 +1,505 @@
+//! Implements the `FileAcl` document type.
+
+use crate::aclentry::AclEntry;
+use crate::failx::path_err;
+#[cfg(not(target_os = "macos"))]
+use crate::flag::Flag;
+use crate::format;
+use crate::unix;
+use crate::{getfacl, setfacl, AclOption};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Type of file described by a [`FileAcl`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum FileKind {
+    /// Regular file.
+    File,
+    /// Directory.
+    Directory,
+    /// Symbolic link.
+    Symlink,
+    /// Block device.
+    BlockDevice,
+    /// Character device.
+    CharDevice,
+    /// Named pipe.
+    Fifo,
+    /// Unix domain socket.
+    Socket,
+}
+
+const KIND_NAMES: &[(FileKind, &str)] = &[
+    (FileKind::File, "file"),
+    (FileKind::Directory, "directory"),
+    (FileKind::Symlink, "symlink"),
+    (FileKind::BlockDevice, "blockdevice"),
+    (FileKind::CharDevice, "chardevice"),
+    (FileKind::Fifo, "fifo"),
+    (FileKind::Socket, "socket"),
+];
+
+impl From<fs::FileType> for FileKind {
+    fn from(file_type: fs::FileType) -> Self {
+        if file_type.is_dir() {
+            FileKind::Directory
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_block_device() {
+            FileKind::BlockDevice
+        } else if file_type.is_char_device() {
+            FileKind::CharDevice
+        } else if file_type.is_fifo() {
+            FileKind::Fifo
+        } else if file_type.is_socket() {
+            FileKind::Socket
+        } else {
+            FileKind::File
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = KIND_NAMES
+            .iter()
+            .find(|item| item.0 == *self)
+            .map_or("file", |item| item.1);
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for FileKind {
+    type Err = format::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match KIND_NAMES.iter().find(|item| item.1 == s.trim()) {
+            Some((kind, _)) => Ok(*kind),
+            None => Err(format::Error::Message(format!(
+                "unknown file type `{}`",
+                s.trim()
+            ))),
+        }
+    }
+}
+
+/// ACL of a file, together with the file's ownership and mode.
+///
+/// A bare list of [`AclEntry`] is hard to interpret on its own: entries with
+/// an empty name refer to the file's owner and group owner. A `FileAcl` keeps
+/// the entries together with the metadata needed to make sense of them.
+///
+/// The access ACL and the default ACL are stored separately. Default ACL
+/// entries keep their [`Flag::DEFAULT`](crate::Flag) flag.
+///
+/// # Text Format
+///
+/// A `FileAcl` is displayed as a header of comment lines, followed by the
+/// entries in the format used by [`to_writer`](crate::to_writer). Since the
+/// header consists of comments, the text can also be read by
+/// [`from_reader`](crate::from_reader).
+///
+/// ```text
+/// # file: tmp/foo
+/// # type: directory
+/// # owner: chip
+/// # uid: 501
+/// # group: staff
+/// # gid: 20
+/// # mode: 0755
+/// allow::user::read,write,execute
+/// allow::group::read,execute
+/// allow::other::read,execute
+/// allow:default:user:chet:read
+/// ```
+///
+/// Backslash, white space and control characters in the path are written as
+/// a backslash followed by three octal digits. Bytes that are not valid UTF-8
+/// are escaped the same way. The serde representation uses the same escaped
+/// string for the path, so any path can be serialized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct FileAcl {
+    /// Path of the file.
+    #[cfg_attr(feature = "serde", serde(with = "path_serde"))]
+    pub path: PathBuf,
+
+    /// Type of the file.
+    pub file_type: FileKind,
+
+    /// User id of the file's owner.
+    pub uid: u32,
+
+    /// Name of the file's owner (or decimal id if not known).
+    pub owner: String,
+
+    /// Group id of the file's group owner.
+    pub gid: u32,
+
+    /// Name of the file's group owner (or decimal id if not known).
+    pub group: String,
+
+    /// Permission bits of the file mode, including setuid, setgid and sticky.
+    pub mode: u32,
+
+    /// Entries of the access ACL.
+    pub access: Vec<AclEntry>,
+
+    /// Entries of the default ACL.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub default: Vec<AclEntry>,
+}
+
+impl FileAcl {
+    /// Read the ACL, ownership and mode of a file or directory.
+    ///
+    /// The `options` are passed to [`getfacl`]. If `options` contains
+    /// [`AclOption::SYMLINK_ACL`], the metadata of a symlink itself is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn read<P, O>(path: P, options: O) -> io::Result<FileAcl>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+    {
+        let path = path.as_ref();
+        let options = options.into().unwrap_or_default();
+
+        let metadata = if options.contains(AclOption::SYMLINK_ACL) {
+            fs::symlink_metadata(path)
+        } else {
+            fs::metadata(path)
+        }
+        .map_err(|err| path_err(path, &err))?;
+
+        let entries = getfacl(path, options)?;
+        let (access, default) = split_default(entries);
+
+        Ok(FileAcl {
+            path: path.to_path_buf(),
+            file_type: metadata.file_type().into(),
+            uid: metadata.uid(),
+            owner: unix::uid_to_name(metadata.uid())?,
+            gid: metadata.gid(),
+            group: unix::gid_to_name(metadata.gid())?,
+            mode: metadata.mode() & 0o7777,
+            access,
+            default,
+        })
+    }
+
+    /// Return the access and default entries as one list.
+    ///
+    /// The result is suitable for passing to [`setfacl`](crate::setfacl).
+    #[must_use]
+    pub fn entries(&self) -> Vec<AclEntry> {
+        let mut result = self.access.clone();
+        result.extend_from_slice(&self.default);
+        result
+    }
+
+    /// Apply the ownership, mode and ACL to the file at `path`.
+    ///
+    /// The owner and group are looked up by name first, falling back to
+    /// `uid` and `gid` if the name is not known on this system. Ownership
+    /// and mode are only changed if they differ from the file's current
+    /// values, so applying a `FileAcl` to an unchanged file does not
+    /// require extra privileges.
+    ///
+    /// The `path` field is ignored.
+    ///
+    /// A symlink at `path` is not followed, and the file at `path` must have
+    /// the type given by `file_type`. On Linux, the ACL is set with
+    /// [`AclOption::NO_FOLLOW`], so no component of `path` may be a symlink.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure, or if the file type differs.
+    pub fn apply<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let metadata = fs::symlink_metadata(path).map_err(|err| path_err(path, &err))?;
+
+        let file_type = FileKind::from(metadata.file_type());
+        if file_type != self.file_type {
+            let msg = format!("expected {}, found {}", self.file_type, file_type);
+            return Err(path_err(path, &io::Error::other(msg)));
+        }
+
+        let uid = unix::name_to_uid(&self.owner).unwrap_or(self.uid);
+        let gid = unix::name_to_gid(&self.group).unwrap_or(self.gid);
+        if metadata.uid() != uid || metadata.gid() != gid {
+            std::os::unix::fs::lchown(
+                path,
+                (metadata.uid() != uid).then_some(uid),
+                (metadata.gid() != gid).then_some(gid),
+            )
+            .map_err(|err| path_err(path, &err))?;
+        }
+
+        // The mode of a symlink cannot be changed, and `set_permissions`
+        // would follow it.
+        if file_type != FileKind::Symlink && metadata.mode() & 0o7777 != self.mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(self.mode))
+                .map_err(|err| path_err(path, &err))?;
+        }
+
+        setfacl(&[path], &self.entries(), NO_FOLLOW_OPTIONS)
+    }
+}
+
+/// Option that keeps `setfacl` from following a symlink.
+#[cfg(target_os = "linux")]
+const NO_FOLLOW_OPTIONS: AclOption = AclOption::NO_FOLLOW;
+
+/// Option that keeps `setfacl` from following a symlink.
+#[cfg(not(target_os = "linux"))]
+const NO_FOLLOW_OPTIONS: AclOption = AclOption::SYMLINK_ACL;
+
+/// Split entries into the access ACL and the default ACL.
+#[cfg(not(target_os = "macos"))]
+fn split_default(entries: Vec<AclEntry>) -> (Vec<AclEntry>, Vec<AclEntry>) {
+    entries
+        .into_iter()
+        .partition(|entry| !entry.flags.contains(Flag::DEFAULT))
+}
+
+/// Split entries into the access ACL and the default ACL.
+#[cfg(target_os = "macos")]
+fn split_default(entries: Vec<AclEntry>) -> (Vec<AclEntry>, Vec<AclEntry>) {
+    (entries, Vec::new())
+}
+
+impl fmt::Display for FileAcl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# file: {}", escape_path(&self.path))?;
+        writeln!(f, "# type: {}", self.file_type)?;
+        writeln!(f, "# owner: {}", self.owner)?;
+        writeln!(f, "# uid: {}", self.uid)?;
+        writeln!(f, "# group: {}", self.group)?;
+        writeln!(f, "# gid: {}", self.gid)?;
+        writeln!(f, "# mode: {:04o}", self.mode)?;
+        for entry in self.access.iter().chain(&self.default) {
+            writeln!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for FileAcl {
+    type Err = format::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut path = None;
+        let mut file_type = None;
+        let mut owner = None;
+        let mut uid = None;
+        let mut group = None;
+        let mut gid = None;
+        let mut mode = None;
+        let mut entries = Vec::new();
+
+        for line in s.lines() {
+            if let Some(header) = line.trim().strip_prefix('#') {
+                let Some((key, value)) = header.split_once(':') else {
+                    continue;
+                };
+                let value = value.trim();
+                match key.trim() {
+                    "file" => path = Some(unescape_path(value)?),
+                    "type" => file_type = Some(value.parse::<FileKind>()?),
+                    "owner" => owner = Some(value.to_string()),
+                    "uid" => uid = Some(parse_number(value, 10)?),
+                    "group" => group = Some(value.to_string()),
+                    "gid" => gid = Some(parse_number(value, 10)?),
+                    "mode" => mode = Some(parse_number(value, 8)?),
+                    _ => (),
+                }
+                continue;
+            }
+
+            let src_line = line.find('#').map_or(line, |n| &line[0..n]).trim();
+            if !src_line.is_empty() {
+                entries.push(src_line.parse::<AclEntry>()?);
+            }
+        }
+
+        let (access, default) = split_default(entries);
+
+        Ok(FileAcl {
+            path: required(path, "file")?,
+            file_type: required(file_type, "type")?,
+            uid: required(uid, "uid")?,
+            owner: required(owner, "owner")?,
+            gid: required(gid, "gid")?,
+            group: required(group, "group")?,
+            mode: required(mode, "mode")?,
+            access,
+            default,
+        })
+    }
+}
+
+/// Return the value of a required header, or an error if it is missing.
+fn required<T>(value: Option<T>, key: &str) -> Result<T, format::Error> {
+    value.ok_or_else(|| format::Error::Message(format!("missing `{key}` header")))
+}
+
+/// Parse a number in the given radix.
+fn parse_number(value: &str, radix: u32) -> Result<u32, format::Error> {
+    u32::from_str_radix(value, radix)
+        .map_err(|_| format::Error::Message(format!("invalid number `{value}`")))
+}
+
+/// Escape a path for the text format.
+fn escape_path(path: &Path) -> String {
+    let bytes = path.as_os_str().as_bytes();
+
+    let mut result = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            if ch == '\\' || ch.is_whitespace() || ch.is_control() {
+                let mut buf = [0; 4];
+                for byte in ch.encode_utf8(&mut buf).bytes() {
+                    result.push_str(&format!("\\{byte:03o}"));
+                }
+            } else {
+                result.push(ch);
+            }
+        }
+        for byte in chunk.invalid() {
+            result.push_str(&format!("\\{byte:03o}"));
+        }
+    }
+    result
+}
+
+/// Reverse the escaping done by `escape_path`.
+fn unescape_path(value: &str) -> Result<PathBuf, format::Error> {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            let byte = bytes
+                .get(i + 1..i + 4)
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok())
+                .ok_or_else(|| format::Error::Message(format!("invalid escape in `{value}`")))?;
+            result.push(byte);
+            i += 4;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Ok(PathBuf::from(OsString::from_vec(result)))
+}
+
+/// Serialize a path as a string escaped by `escape_path`.
+#[cfg(feature = "serde")]
+mod path_serde {
+    use super::{escape_path, unescape_path};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::path::{Path, PathBuf};
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&escape_path(path))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        unescape_path(&value).map_err(de::Error::custom)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod fileacl_tests {
+    use super::*;
+    use crate::Perm;
+
+    fn sample() -> FileAcl {
+        #[cfg(not(target_os = "macos"))]
+        let default = vec![AclEntry::allow_user("chet", Perm::READ, Flag::DEFAULT)];
+        #[cfg(target_os = "macos")]
+        let default = Vec::new();
+
+        FileAcl {
+            path: PathBuf::from("tmp/a b\\c"),
+            file_type: FileKind::Directory,
+            uid: 501,
+            owner: String::from("chip"),
+            gid: 20,
+            group: String::from("staff"),
+            mode: 0o1755,
+            access: vec![AclEntry::allow_group("admin", Perm::READ, None)],
+            default,
+        }
+    }
+
+    #[test]
+    fn test_fileacl_text() {
+        let acl = sample();
+        let text = acl.to_string();
+        assert!(text.starts_with(
+            "# file: tmp/a\\040b\\134c\n# type: directory\n# owner: chip\n# uid: 501\n# group: staff\n# gid: 20\n# mode: 1755\nallow::group:admin:read\n"
+        ));
+        assert_eq!(text.parse::<FileAcl>().unwrap(), acl);
+
+        // Header is made of comments, so the entries are readable by themselves.
+        let entries = crate::from_str(&text).unwrap();
+        assert_eq!(entries, acl.entries());
+
+        let err = "# file: x\nuser::r\n".parse::<FileAcl>().unwrap_err();
+        assert_eq!(err.to_string(), "missing `type` header");
+
+        let err = "# file: x\\9\n".parse::<FileAcl>().unwrap_err();
+        assert_eq!(err.to_string(), "invalid escape in `x\\9`");
+    }
+
+    #[test]
+    fn test_escape_path() {
+        let path = PathBuf::from(OsString::from_vec(b"a\n\xffz".to_vec()));
+        let escaped = escape_path(&path);
+        assert_eq!(escaped, "a\\012\\377z");
+        assert_eq!(unescape_path(&escaped).unwrap(), path);
+
+        assert_eq!(escape_path(Path::new("caf\u{e9}")), "caf\u{e9}");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_fileacl_serde() {
+        let acl = sample();
+        let json = serde_json::to_string(&acl).unwrap();
+        assert!(json.starts_with(
+            r#"{"path":"tmp/a\\040b\\134c","file_type":"directory","uid":501,"owner":"chip","gid":20,"group":"staff","mode":1005,"access":[{"kind":"group","name":"admin","perms":["read"]"#
+        ));
+        assert_eq!(serde_json::from_str::<FileAcl>(&json).unwrap(), acl);
+
+        let mut acl = sample();
+        acl.path = PathBuf::from(OsString::from_vec(b"a\xffz".to_vec()));
+        let json = serde_json::to_string(&acl).unwrap();
+        assert!(json.starts_with(r#"{"path":"a\\377z","#));
+        assert_eq!(serde_json::from_str::<FileAcl>(&json).unwrap(), acl);
+
+        let err = serde_json::from_str::<FileAcl>(r#"{"path":"x\\9"}"#).unwrap_err();
+        assert!(err.to_string().starts_with("invalid escape in `x\\9`"));
+    }
+}
@@ -8,7 +8,7 @@ use crate::aclentry::AclEntryKind;
 use crate::flag::FlagName;
 use crate::perm::PermName;
 
-const ACLENTRYKINDS: &'static [(AclEntryKind, &'static str)] = &[
+const ACLENTRYKINDS: &[(AclEntryKind, &str)] = &[
     (AclEntryKind::User, "user"),
     (AclEntryKind::Group, "group"),
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
@@ -20,7 +20,7 @@ const ACLENTRYKINDS: &'static [(AclEntryKind, &'static str)] = &[
     (AclEntryKind::Unknown, "unknown"),
 ];
 
-const FLAGS: &'static [(FlagName, &'static str)] = &[
+const FLAGS: &[(FlagName, &str)] = &[
     #[cfg(any(target_os = "macos", target_os = "freebsd"))]
     (FlagName::inherited, "inherited"),
     #[cfg(any(target_os = "macos", target_os = "freebsd"))]
@@ -35,7 +35,7 @@ const FLAGS: &'static [(FlagName, &'static str)] = &[
     (FlagName::default, "default"),
 ];
 
-const PERMS: &'static [(PermName, &'static str)] = &[
+const PERMS: &[(PermName, &str)] = &[
     (PermName::read, "read"),
     (PermName::write, "write"),
     (PermName::execute, "execute"),
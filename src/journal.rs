@@ -0,0 +1,336 @@
+//! Implements an undo journal for bulk ACL changes.
+
+use crate::acl::AclOption;
+use crate::aclentry::AclEntry;
+use crate::failx::{custom_err, path_err};
+use crate::swap::with_open_path;
+use crate::{getfacl, setfacl};
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Previous ACL of a file, as recorded in a [`Journal`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JournalRecord {
+    /// Path of the file.
+    pub path: PathBuf,
+
+    /// Device number of the file.
+    pub dev: u64,
+
+    /// Inode number of the file.
+    pub ino: u64,
+
+    /// ACL entries of the file before the change.
+    pub entries: Vec<AclEntry>,
+
+    /// True if the ACL is the symlink's own, as with
+    /// [`AclOption::SYMLINK_ACL`].
+    #[serde(default)]
+    pub symlink_acl: bool,
+
+    /// True if no component of the path may be a symlink, as with
+    /// [`AclOption::NO_FOLLOW`].
+    #[serde(default)]
+    pub no_follow: bool,
+}
+
+impl JournalRecord {
+    /// Return the options to read or write the recorded ACL with.
+    fn options(&self) -> AclOption {
+        let mut options = AclOption::empty();
+        options.set(AclOption::SYMLINK_ACL, self.symlink_acl);
+        options.set(AclOption::NO_FOLLOW, self.no_follow);
+        options
+    }
+}
+
+/// Writer for an undo journal.
+///
+/// Before an ACL is changed, the journal records the file's path, inode
+/// identity and current ACL entries as one line of JSON. Each record is
+/// flushed to the writer as soon as it is written, so a buffered writer does
+/// not hold back the record of a change that has already been made. The
+/// journal can later be passed to [`undo`] to restore every recorded ACL.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::{getfacl, undo, Journal};
+///
+/// let file = std::fs::File::create("./undo.jsonl")?;
+/// let mut journal = Journal::new(std::io::BufWriter::new(file));
+///
+/// let entries = getfacl("./tmp/template", None)?;
+/// journal.setfacl(&["./tmp/foo", "./tmp/bar"], &entries, None)?;
+///
+/// // Later...
+/// undo(std::fs::File::open("./undo.jsonl")?)?;
+/// # Ok(()) }
+/// ```
+pub struct Journal<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> Journal<W> {
+    /// Create a journal that writes records to `writer`.
+    pub fn new(writer: W) -> Journal<W> {
+        Journal { writer }
+    }
+
+    /// Record the current ACL of a file.
+    ///
+    /// Call this before changing the ACL of `path`, with the options that
+    /// the change uses. Only [`AclOption::SYMLINK_ACL`] and
+    /// [`AclOption::NO_FOLLOW`] are kept; the whole ACL is always recorded.
+    /// The record is flushed before this returns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn record<P, O>(&mut self, path: P, options: O) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+    {
+        let path = path.as_ref();
+        let options = options.into().unwrap_or_default();
+        let (dev, ino, entries) = with_open_path(path, options, |open_path, open_options| {
+            let (dev, ino) = file_id(open_path, open_options)?;
+            let entries = getfacl(open_path, open_options & AclOption::SYMLINK_ACL)?;
+            Ok((dev, ino, entries))
+        })?;
+
+        let record = JournalRecord {
+            path: path.to_path_buf(),
+            dev,
+            ino,
+            entries,
+            symlink_acl: options.contains(AclOption::SYMLINK_ACL),
+            no_follow: options.contains(AclOption::NO_FOLLOW),
+        };
+
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    /// Record the current ACL of each path, then call [`setfacl`].
+    ///
+    /// Each path is recorded just before it is changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn setfacl<P, O>(&mut self, paths: &[P], entries: &[AclEntry], options: O) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+    {
+        let options = options.into().unwrap_or_default();
+        for path in paths {
+            self.record(path, options)?;
+            setfacl(&[path], entries, options)?;
+        }
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Result of [`undo`].
+#[derive(Debug, Default)]
+pub struct UndoReport {
+    /// Paths whose previous ACL was restored.
+    pub restored: Vec<PathBuf>,
+
+    /// Paths that were skipped because the file no longer exists or is a
+    /// different file (different device or inode number).
+    pub skipped: Vec<PathBuf>,
+
+    /// Paths whose previous ACL could not be restored, with the error.
+    pub failures: Vec<(PathBuf, io::Error)>,
+}
+
+impl UndoReport {
+    /// Return true if no recorded ACL failed to be restored.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Restore the ACLs recorded in a journal.
+///
+/// Records are applied in reverse order, so if a file was recorded more than
+/// once, it ends up with the ACL it had before the first change. A file is
+/// skipped if it no longer exists, or if its device and inode number differ
+/// from the record.
+///
+/// # Linux
+///
+/// Each file is opened once. The device and inode number are checked on the
+/// open file, and the ACL is written through it, so a file swapped in after
+/// the check is not changed.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the journal cannot be read or parsed. Nothing
+/// is restored in that case. A record that cannot be restored does not stop
+/// the undo; it is listed in [`UndoReport::failures`].
+pub fn undo<R: io::Read>(reader: R) -> io::Result<UndoReport> {
+    let mut records = Vec::new();
+    for (i, line_result) in io::BufReader::new(reader).lines().enumerate() {
+        let line = line_result?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str::<JournalRecord>(&line)
+            .map_err(|err| custom_err(&format!("line {}", i + 1), &err.into()))?;
+        records.push(record);
+    }
+
+    let mut report = UndoReport::default();
+    for record in records.into_iter().rev() {
+        match restore(&record) {
+            Ok(true) => report.restored.push(record.path),
+            Ok(false) => report.skipped.push(record.path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => report.skipped.push(record.path),
+            Err(err) => report.failures.push((record.path, err)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Restore one record. Return false if the file is a different one.
+fn restore(record: &JournalRecord) -> io::Result<bool> {
+    with_open_path(&record.path, record.options(), |open_path, options| {
+        if file_id(open_path, options)? != (record.dev, record.ino) {
+            return Ok(false);
+        }
+        setfacl(&[open_path], &record.entries, options)?;
+        Ok(true)
+    })
+}
+
+/// Return the device and inode number of a file.
+///
+/// With [`AclOption::SYMLINK_ACL`], a symlink is not followed.
+fn file_id(path: &Path, options: AclOption) -> io::Result<(u64, u64)> {
+    let metadata = if options.contains(AclOption::SYMLINK_ACL) {
+        fs::symlink_metadata(path)
+    } else {
+        fs::metadata(path)
+    };
+    let metadata = metadata.map_err(|err| path_err(path, &err))?;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, any(target_os = "linux", target_os = "freebsd")))]
+mod journal_tests {
+    use super::*;
+    use crate::from_mode;
+
+    #[test]
+    fn test_journal_undo() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file1 = dir.path().join("file1");
+        let file2 = dir.path().join("file2");
+        fs::write(&file1, "")?;
+        fs::write(&file2, "")?;
+        setfacl(&[&file1, &file2], &from_mode(0o640), None)?;
+
+        let mut journal = Journal::new(Vec::new());
+        journal.setfacl(&[&file1, &file2], &from_mode(0o600), None)?;
+        journal.setfacl(&[&file1], &from_mode(0o666), None)?;
+        let text = journal.into_inner();
+        assert_eq!(text.split(|ch| *ch == b'\n').count(), 4);
+
+        // Replace file2 with a different file.
+        let replacement = dir.path().join("replacement");
+        fs::write(&replacement, "")?;
+        setfacl(&[&replacement], &from_mode(0o600), None)?;
+        fs::rename(&replacement, &file2)?;
+
+        let report = undo(&text[..])?;
+        assert_eq!(report.restored, [file1.clone(), file1.clone()]);
+        assert_eq!(report.skipped, [file2]);
+        assert_eq!(getfacl(&file1, None)?, from_mode(0o640));
+        assert_eq!(getfacl(&report.skipped[0], None)?, from_mode(0o600));
+        assert!(report.is_complete());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_failures() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        fs::write(&file, "")?;
+        setfacl(&[&file], &from_mode(0o640), None)?;
+
+        let mut journal = Journal::new(Vec::new());
+        journal.record(&file, None)?;
+        journal.record(&file, AclOption::NO_FOLLOW)?;
+        let mut text = journal.into_inner();
+
+        // A record that cannot be restored is reported, and undo goes on.
+        let metadata = fs::metadata(&file)?;
+        let bad = JournalRecord {
+            path: file.clone(),
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            entries: vec![AclEntry::allow_other(
+                crate::Perm::READ,
+                crate::Flag::DEFAULT,
+            )],
+            symlink_acl: false,
+            no_follow: false,
+        };
+        serde_json::to_writer(&mut text, &bad)?;
+
+        setfacl(&[&file], &from_mode(0o600), None)?;
+        let report = undo(&text[..])?;
+        assert_eq!(report.restored, [file.clone(), file.clone()]);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, file);
+        assert!(!report.is_complete());
+        assert_eq!(getfacl(&file, None)?, from_mode(0o640));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_record_flushes() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut journal = Journal::new(io::BufWriter::new(Vec::new()));
+        journal.record(dir.path(), None)?;
+
+        // The record reached the inner writer without an explicit flush.
+        let buffered = journal.into_inner();
+        assert!(buffered.buffer().is_empty());
+        assert!(buffered.get_ref().ends_with(b"\n"));
+
+        Ok(())
+    }
+}
@@ -40,38 +40,113 @@
 //! [`AclEntry`] structure contains five fields:
 //!
 //! - kind : [`AclEntryKind`] - the kind of entry (User, Group, Other, Mask,
-//!     or Unknown).
+//!   or Unknown).
 //! - name : [`String`] - name of the principal being given access. You can
-//!     use a user/group name, decimal uid/gid, or UUID (on macOS).
+//!   use a user/group name, decimal uid/gid, or UUID (on macOS).
 //! - perms : [`Perm`] - permission bits for the entry.
 //! - flags : [`Flag`] - flags indicating whether an entry is inherited, etc.
 //! - allow : [`bool`] - true if entry is allowed; false means deny. Linux only
-//!     supports allow=true.
+//!   supports allow=true.
+//!
+//! The kinds, permissions and flags available in [`AclEntry`] depend on the
+//! platform. To store or validate an ACL from another platform, use
+//! [`PortableAclEntry`]. Every kind, permission and flag exists in the
+//! portable types on every platform. Permission and flag names that are not
+//! recognized at all are kept as data rather than rejected.
+//!
+//! To read the ACL together with the file's owner, group owner, mode and
+//! type, use [`FileAcl::read`]. With the `serde` feature, [`snapshot`] and
+//! [`restore`] save and reapply the ACLs of a whole directory tree using a
+//! JSON Lines manifest, and [`Journal`] records previous ACLs so that a bulk
+//! change can be reverted with [`undo`].
+//!
+//! With the `tokio` feature, the [`tokio`](crate::tokio) module provides an
+//! async API that runs calls on tokio's blocking thread pool.
 
 #![warn(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 mod acl;
 mod aclentry;
+#[cfg(target_os = "linux")]
+mod at;
+mod audit;
+mod batch;
 mod bindings;
 mod bititer;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+mod builder;
+mod copy;
+mod diff;
 mod failx;
+mod fileacl;
 mod flag;
 mod format;
+#[cfg(feature = "serde")]
+mod journal;
+mod names;
+mod nofollow;
+mod orphan;
+mod pax;
 mod perm;
+mod policy;
+mod portable;
+mod principal;
 mod qualifier;
+mod raw;
+mod replace;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod support;
+mod swap;
 mod sys;
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub mod tokio;
+mod transaction;
 mod unix;
 mod util;
+mod walk;
 
-// Export AclOption, AclEntry, AclEntryKind, Flag and Perm.
+// Export AclOption, AclEntry, AclEntryKind, FileAcl, FileKind, Flag and Perm.
 pub use acl::AclOption;
 pub use aclentry::{AclEntry, AclEntryKind};
+#[cfg(target_os = "linux")]
+pub use at::{getfacl_at, setfacl_at};
+pub use audit::{audit, audit_entries, AuditFinding, FindingKind, Severity};
+pub use batch::getfacl_batch;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+pub use builder::AclBuilder;
+pub use copy::{copy_tree_with_acl, copy_with_acl, CopyReport};
+pub use diff::{diff, AclDiff, AclEntryChange};
+pub use fileacl::{FileAcl, FileKind};
 pub use flag::Flag;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub use journal::{undo, Journal, JournalRecord, UndoReport};
+pub use nofollow::SymlinkError;
+pub use orphan::{
+    clean_orphans, clean_orphans_tree, find_orphans, find_orphans_tree, OrphanAction,
+};
+pub use pax::{from_pax_headers, to_pax_headers, PAX_ACL_ACCESS, PAX_ACL_ACE, PAX_ACL_DEFAULT};
 pub use perm::Perm;
+pub use policy::{AclPolicy, PolicyRule, PolicyViolation, ViolationKind};
+pub use portable::{
+    AclPlatform, PortableAclEntry, PortableAclEntryKind, PortableFlag, PortablePerm,
+};
+pub use principal::Principal;
+pub use raw::{getfacl_raw, RawAcl, RawAclEntry, RawEntries};
+pub use replace::{replace_principal, replace_principal_tree};
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub use snapshot::{restore, snapshot};
+pub use support::{acl_support, has_extended_acl, AclSupport};
+pub use swap::{setfacl_if_unchanged, AclMismatch};
+pub use transaction::RollbackReport;
 
 use acl::Acl;
 use failx::custom_err;
+use names::NameCache;
 use std::io::{self, BufRead};
 use std::path::Path;
 
@@ -107,6 +182,8 @@ use failx::fail_custom;
 ///
 /// If `path` points to a symlink, `getfacl` returns the ACL of the file pointed
 /// to by the symlink. [`AclOption::SYMLINK_ACL`] is not supported on Linux.
+/// With [`AclOption::NO_FOLLOW`], `getfacl` fails if any component of `path`
+/// is a symlink, and the error wraps a [`SymlinkError`].
 ///
 /// [`AclOption::DEFAULT_ACL`] causes `getfacl` to only include entries for the
 /// default ACL, if present for a directory path. When called with
@@ -131,34 +208,46 @@ where
     P: AsRef<Path>,
     O: Into<Option<AclOption>>,
 {
-    _getfacl(path.as_ref(), options.into().unwrap_or_default())
+    _getfacl(path.as_ref(), options.into().unwrap_or_default(), None)
 }
 
+fn _getfacl(
+    path: &Path,
+    options: AclOption,
+    names: Option<&NameCache>,
+) -> io::Result<Vec<AclEntry>> {
+    let mut entries = Vec::new();
+    for acl in read_acls(path, options)? {
+        entries.append(&mut acl.entries_cached(names)?);
+    }
+    Ok(entries)
+}
+
+/// Read the ACL's that `getfacl` returns for `path`.
 #[cfg(target_os = "macos")]
-fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
-    Acl::read(path, options)?.entries()
+fn read_acls(path: &Path, options: AclOption) -> io::Result<Vec<Acl>> {
+    Ok(vec![Acl::read(path, options)?])
 }
 
+/// Read the ACL's that `getfacl` returns for `path`: the access ACL, followed
+/// by the default ACL if it is a Posix.1e ACL.
 #[cfg(not(target_os = "macos"))]
-fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
+fn read_acls(path: &Path, options: AclOption) -> io::Result<Vec<Acl>> {
     if options.contains(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
         fail_custom("ACCESS_ACL and DEFAULT_ACL are mutually exclusive options")
     } else if options.intersects(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
-        Acl::read(path, options)?.entries()
+        Ok(vec![Acl::read(path, options)?])
     } else {
         let acl = Acl::read(path, options)?;
-        let mut entries = acl.entries()?;
-
-        if acl.is_posix() {
-            let mut default = Acl::read(
-                path,
-                options | AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR,
-            )?
-            .entries()?;
-
-            entries.append(&mut default);
+        if !acl.is_posix() {
+            return Ok(vec![acl]);
         }
-        Ok(entries)
+
+        let default = Acl::read(
+            path,
+            options | AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR,
+        )?;
+        Ok(vec![acl, default])
     }
 }
 
@@ -207,6 +296,9 @@ fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
 /// is desired. When `setfacl` is called with no [`Flag::DEFAULT`] entries, it
 /// deletes the default ACL.
 ///
+/// With [`AclOption::NO_FOLLOW`], `setfacl` fails if any component of a path
+/// is a symlink, and the error wraps a [`SymlinkError`].
+///
 /// ### Linux Example
 ///
 /// ```ignore
@@ -224,6 +316,24 @@ fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
 /// # Ok(()) }
 /// ```
 ///
+/// # Unknown Entries
+///
+/// `getfacl` returns an entry with an unrecognized tag as an
+/// [`AclEntryKind::Unknown`] entry, with a name like "@tag 42". The native
+/// ACL libraries do not accept such tags, so these entries cannot be written
+/// back, and `setfacl` fails. To modify an ACL that has unknown entries, use
+/// [`AclOption::DROP_UNKNOWN`]: `setfacl` leaves the unknown entries out and
+/// logs a warning for each one with the [`log`] crate. To get the entries
+/// that are left out, call [`split_unknown`] first.
+///
+/// # Transactions
+///
+/// By default, `setfacl` stops at the first path that fails, leaving earlier
+/// paths changed. With [`AclOption::TRANSACTIONAL`], the previous ACL of each
+/// path is saved before it is written. If any path fails, every path already
+/// changed is restored, and the error wraps a [`RollbackReport`] describing
+/// what was rolled back.
+///
 /// # Errors
 ///
 /// Returns an [`io::Error`] on failure.
@@ -233,15 +343,64 @@ where
     P: AsRef<Path>,
     O: Into<Option<AclOption>>,
 {
-    _setfacl(paths, entries, options.into().unwrap_or_default())
+    let options = options.into().unwrap_or_default();
+    let dropped;
+    let entries = if options.contains(AclOption::DROP_UNKNOWN) {
+        dropped = drop_unknown(entries);
+        &dropped
+    } else {
+        entries
+    };
+
+    if options.contains(AclOption::TRANSACTIONAL) {
+        transaction::setfacl_transactional(paths, entries, options)
+    } else {
+        _setfacl(paths, entries, options)
+    }
+}
+
+/// Split ACL entries into the entries `setfacl` can write and the entries of
+/// kind [`AclEntryKind::Unknown`].
+///
+/// The first list is what `setfacl` writes with [`AclOption::DROP_UNKNOWN`];
+/// the second is what it drops. Both keep the order of `entries`.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::{getfacl, setfacl, split_unknown};
+///
+/// let (entries, unknown) = split_unknown(&getfacl("./tmp/foo", None)?);
+/// for entry in &unknown {
+///     eprintln!("cannot keep {entry}");
+/// }
+/// setfacl(&["./tmp/foo"], &entries, None)?;
+/// # Ok(()) }
+/// ```
+#[must_use]
+pub fn split_unknown(entries: &[AclEntry]) -> (Vec<AclEntry>, Vec<AclEntry>) {
+    entries
+        .iter()
+        .cloned()
+        .partition(|entry| entry.kind != AclEntryKind::Unknown)
+}
+
+/// Return the entries that are not of kind `Unknown`, and log a warning for
+/// each one that is dropped.
+pub(crate) fn drop_unknown(entries: &[AclEntry]) -> Vec<AclEntry> {
+    let (known, unknown) = split_unknown(entries);
+    for entry in &unknown {
+        log::warn!("Dropping unknown ACL entry: {entry}");
+    }
+    known
 }
 
-#[cfg(target_os = "macos")]
 fn _setfacl<P>(paths: &[P], entries: &[AclEntry], options: AclOption) -> io::Result<()>
 where
     P: AsRef<Path>,
 {
-    let acl = Acl::from_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
+    let acl = PreparedAcl::new(entries, options)?;
     for path in paths {
         acl.write(path.as_ref(), options)?;
     }
@@ -249,30 +408,61 @@ where
     Ok(())
 }
 
-#[cfg(not(target_os = "macos"))]
-fn _setfacl<P>(paths: &[P], entries: &[AclEntry], options: AclOption) -> io::Result<()>
-where
-    P: AsRef<Path>,
-{
-    if options.contains(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
-        fail_custom("ACCESS_ACL and DEFAULT_ACL are mutually exclusive options")?;
-    } else if options.intersects(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
+/// ACL built from entries, ready to be written to any number of files.
+///
+/// Building the ACL checks the entries, so an invalid ACL is reported before
+/// any file is changed.
+pub(crate) struct PreparedAcl {
+    acl: Acl,
+    #[cfg(not(target_os = "macos"))]
+    default_acl: Option<Acl>,
+}
+
+impl PreparedAcl {
+    /// Build the ACL for `entries`.
+    #[cfg(target_os = "macos")]
+    pub(crate) fn new(entries: &[AclEntry], _options: AclOption) -> io::Result<PreparedAcl> {
         let acl = Acl::from_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
+        Ok(PreparedAcl { acl })
+    }
 
-        for path in paths {
-            acl.write(path.as_ref(), options)?;
-        }
-    } else {
-        let (access_acl, default_acl) =
-            Acl::from_unified_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
+    /// Build the ACL for `entries`.
+    #[cfg(not(target_os = "macos"))]
+    pub(crate) fn new(entries: &[AclEntry], options: AclOption) -> io::Result<PreparedAcl> {
+        if options.contains(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
+            fail_custom("ACCESS_ACL and DEFAULT_ACL are mutually exclusive options")
+        } else if options.intersects(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
+            let acl = Acl::from_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
+            Ok(PreparedAcl {
+                acl,
+                default_acl: None,
+            })
+        } else {
+            let (access_acl, default_acl) = Acl::from_unified_entries(entries)
+                .map_err(|err| custom_err("Invalid ACL", &err))?;
 
-        if access_acl.is_empty() {
-            fail_custom("Invalid ACL: missing required entries")?;
+            if access_acl.is_empty() {
+                fail_custom("Invalid ACL: missing required entries")?;
+            }
+
+            Ok(PreparedAcl {
+                acl: access_acl,
+                default_acl: Some(default_acl),
+            })
         }
+    }
+
+    /// Write the ACL to a file.
+    #[cfg(target_os = "macos")]
+    pub(crate) fn write(&self, path: &Path, options: AclOption) -> io::Result<()> {
+        self.acl.write(path, options)
+    }
 
-        for path in paths {
-            let path = path.as_ref();
-            if access_acl.is_posix() {
+    /// Write the ACL to a file.
+    #[cfg(not(target_os = "macos"))]
+    pub(crate) fn write(&self, path: &Path, options: AclOption) -> io::Result<()> {
+        if let Some(default_acl) = &self.default_acl {
+            if self.acl.is_posix() {
                 // Try to set default acl first. This will fail if path is not
                 // a directory and default_acl is non-empty. This ordering
                 // avoids leaving the file's ACL in a partially changed state
@@ -282,11 +472,9 @@ where
                     options | AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR,
                 )?;
             }
-            access_acl.write(path, options)?;
         }
+        self.acl.write(path, options)
     }
-
-    Ok(())
 }
 
 /// Write ACL entries to text.
@@ -391,7 +579,7 @@ fn trim_comment(line: &str) -> &str {
 pub fn to_string(entries: &[AclEntry]) -> io::Result<String> {
     let mut buf = Vec::<u8>::with_capacity(128);
     to_writer(&mut buf, entries)?;
-    String::from_utf8(buf).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    String::from_utf8(buf).map_err(io::Error::other)
 }
 
 /// Read ACL entries from text.
@@ -415,9 +603,10 @@ pub fn from_str(s: &str) -> io::Result<Vec<AclEntry>> {
 #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
 #[must_use]
 pub fn from_mode(mode: u32) -> Vec<AclEntry> {
+    let [owner, group, other] = Perm::from_mode(mode);
     vec![
-        AclEntry::allow_user("", Perm::from_bits_truncate((mode >> 6) & 7), None),
-        AclEntry::allow_group("", Perm::from_bits_truncate((mode >> 3) & 7), None),
-        AclEntry::allow_other(Perm::from_bits_truncate(mode & 7), None),
+        AclEntry::allow_user("", owner, None),
+        AclEntry::allow_group("", group, None),
+        AclEntry::allow_other(other, None),
     ]
 }
@@ -0,0 +1,94 @@
+//! Implements a cache of user and group names shared between threads.
+
+use crate::qualifier::Qualifier;
+use crate::unix::{self, gid_t, uid_t};
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io;
+use std::sync::{Mutex, PoisonError};
+
+/// Cache of names looked up for uid's and gid's.
+///
+/// Name lookups may go to a network directory service, so a batch of
+/// `getfacl` calls shares one cache instead of looking up the same owner for
+/// every file.
+#[derive(Default)]
+pub(crate) struct NameCache {
+    users: Mutex<HashMap<uid_t, Option<String>>>,
+    groups: Mutex<HashMap<gid_t, Option<String>>>,
+}
+
+impl NameCache {
+    /// Return the name of the user/group, looking it up if not cached.
+    ///
+    /// If the uid or gid has no name, the name is the number as a string.
+    pub fn name(&self, qualifier: &Qualifier) -> io::Result<String> {
+        match qualifier {
+            Qualifier::User(id) | Qualifier::Group(id) => {
+                Ok(self.find_name(qualifier)?.unwrap_or_else(|| id.to_string()))
+            }
+            _ => qualifier.name(),
+        }
+    }
+
+    /// Return the name of the user/group, or `None` if the uid or gid has
+    /// no name.
+    pub fn find_name(&self, qualifier: &Qualifier) -> io::Result<Option<String>> {
+        match qualifier {
+            Qualifier::User(uid) => cached(&self.users, *uid, || unix::find_user_name(*uid)),
+            Qualifier::Group(gid) => cached(&self.groups, *gid, || unix::find_group_name(*gid)),
+            _ => qualifier.name().map(Some),
+        }
+    }
+}
+
+/// Return the value for `key` from `map`, or insert the result of `lookup`.
+///
+/// The lock is not held during the lookup. Failed lookups are not cached.
+fn cached<K, F>(
+    map: &Mutex<HashMap<K, Option<String>>>,
+    key: K,
+    lookup: F,
+) -> io::Result<Option<String>>
+where
+    K: Eq + Hash,
+    F: FnOnce() -> io::Result<Option<String>>,
+{
+    if let Some(name) = map.lock().unwrap_or_else(PoisonError::into_inner).get(&key) {
+        return Ok(name.clone());
+    }
+
+    let name = lookup()?;
+    map.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(key, name.clone());
+    Ok(name)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod names_tests {
+    use super::*;
+
+    #[test]
+    fn test_name_cache() -> io::Result<()> {
+        let cache = NameCache::default();
+        let root = Qualifier::User(0);
+        assert_eq!(cache.name(&root)?, root.name()?);
+        assert_eq!(cache.name(&root)?, root.name()?);
+        assert_eq!(cache.users.lock().unwrap().len(), 1);
+
+        let group = Qualifier::Group(0);
+        assert_eq!(cache.name(&group)?, group.name()?);
+        assert_eq!(cache.groups.lock().unwrap().len(), 1);
+
+        let unnamed = Qualifier::User(4_000_000_001);
+        assert_eq!(cache.find_name(&unnamed)?, None);
+        assert_eq!(cache.name(&unnamed)?, "4000000001");
+        assert_eq!(cache.users.lock().unwrap().len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! Implements the `NO_FOLLOW` option, which refuses to resolve symlinks.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Error returned when a path goes through a symlink.
+///
+/// When an ACL is read or written with [`AclOption::NO_FOLLOW`] and a
+/// component of the path is a symlink, the returned [`io::Error`] wraps a
+/// `SymlinkError`. Use [`SymlinkError::from_error`] to retrieve it, for
+/// example to skip the path rather than stop.
+///
+/// [`AclOption::NO_FOLLOW`]: crate::AclOption::NO_FOLLOW
+#[derive(Debug)]
+pub struct SymlinkError {
+    /// Path that was requested.
+    pub path: PathBuf,
+
+    /// Leading part of `path` that is a symlink.
+    pub link: PathBuf,
+}
+
+impl SymlinkError {
+    /// Return the symlink error wrapped by `err`.
+    #[must_use]
+    pub fn from_error(err: &io::Error) -> Option<&SymlinkError> {
+        err.get_ref()?.downcast_ref::<SymlinkError>()
+    }
+}
+
+impl fmt::Display for SymlinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "File {:?}: refusing to follow symlink {:?}",
+            self.path, self.link
+        )
+    }
+}
+
+impl Error for SymlinkError {}
+
+/// Return an error that wraps a [`SymlinkError`].
+#[cfg(target_os = "linux")]
+pub(crate) fn symlink_err(path: &Path, link: &Path) -> io::Error {
+    io::Error::other(SymlinkError {
+        path: path.to_path_buf(),
+        link: link.to_path_buf(),
+    })
+}
+
+/// Open `path` without following a symlink in any component, and call
+/// `func` with a path that refers to the open file.
+///
+/// Each component is opened relative to the previous one with
+/// `O_PATH | O_NOFOLLOW`, so a symlink cannot be swapped in while the path
+/// is resolved. Errors returned by `func` mention `path`.
+#[cfg(target_os = "linux")]
+pub(crate) fn with_path_no_follow<T, F>(path: &Path, func: F) -> io::Result<T>
+where
+    F: FnOnce(&Path) -> io::Result<T>,
+{
+    use crate::at::{fd_path, with_fd_path};
+    use crate::failx::path_err;
+    use crate::sys::sg;
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::path::Component;
+
+    let open = |path: &Path| {
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(sg::O_PATH | sg::O_NOFOLLOW)
+            .open(path)
+    };
+
+    if path.as_os_str().is_empty() {
+        let err = io::Error::from(io::ErrorKind::NotFound);
+        return Err(path_err(path, &err));
+    }
+
+    let start = if path.has_root() { "/" } else { "." };
+    let mut file = open(Path::new(start)).map_err(|err| path_err(path, &err))?;
+    let mut walked = PathBuf::new();
+
+    for component in path.components() {
+        walked.push(component);
+        let name = match component {
+            Component::Normal(name) => name,
+            Component::ParentDir => Component::ParentDir.as_os_str(),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => continue,
+        };
+
+        file = open(&fd_path(&file).join(name)).map_err(|err| path_err(path, &err))?;
+        if file.metadata()?.is_symlink() {
+            return Err(symlink_err(path, &walked));
+        }
+    }
+
+    with_fd_path(&file, path, func)
+}
+
+/// Refuse the `NO_FOLLOW` option on platforms other than Linux.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn with_path_no_follow<T, F>(_path: &Path, _func: F) -> io::Result<T>
+where
+    F: FnOnce(&Path) -> io::Result<T>,
+{
+    crate::failx::fail_custom("NO_FOLLOW option is only supported on Linux")
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, target_os = "linux"))]
+mod nofollow_tests {
+    use super::*;
+    use crate::acl::AclOption;
+    use crate::{from_mode, getfacl, setfacl};
+    use std::fs;
+
+    #[test]
+    fn test_no_follow() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sub = dir.path().join("sub");
+        let file = sub.join("file");
+        fs::create_dir(&sub)?;
+        fs::write(&file, "")?;
+        std::os::unix::fs::symlink("sub", dir.path().join("dirlink"))?;
+        std::os::unix::fs::symlink("file", sub.join("filelink"))?;
+
+        let options = AclOption::NO_FOLLOW;
+        setfacl(&[&file], &from_mode(0o640), options)?;
+        assert_eq!(getfacl(&file, options)?, from_mode(0o640));
+        assert_eq!(getfacl(&sub, options)?, getfacl(&sub, None)?);
+        assert_eq!(getfacl(sub.join("../sub/file"), options)?, from_mode(0o640));
+
+        // Symlink as the final component.
+        let link = sub.join("filelink");
+        let err = setfacl(&[&link], &from_mode(0o600), options).unwrap_err();
+        let symlink = SymlinkError::from_error(&err).unwrap();
+        assert_eq!(symlink.path, link);
+        assert_eq!(symlink.link, link);
+        assert_eq!(
+            err.to_string(),
+            format!("File {link:?}: refusing to follow symlink {link:?}")
+        );
+
+        // Symlink as a directory component.
+        let path = dir.path().join("dirlink/file");
+        let err = getfacl(&path, options).unwrap_err();
+        let symlink = SymlinkError::from_error(&err).unwrap();
+        assert_eq!(symlink.link, dir.path().join("dirlink"));
+        assert_eq!(getfacl(&file, None)?, from_mode(0o640));
+
+        // Errors mention the original path.
+        let missing = sub.join("missing");
+        let err = getfacl(&missing, options).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("File {missing:?}: ")));
+        let err = getfacl(&file, options | AclOption::DEFAULT_ACL).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("File {file:?}: Permission denied (os error 13)")
+        );
+
+        Ok(())
+    }
+}
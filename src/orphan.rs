@@ -0,0 +1,310 @@
+//! Implements detection and cleanup of entries for deleted accounts.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::failx::fail_custom;
+use crate::names::NameCache;
+use crate::principal::Principal;
+use crate::qualifier::Qualifier;
+use crate::raw::getfacl_raw;
+use crate::replace::{entry_key, reassign_entry};
+use crate::setfacl;
+use crate::walk::Walk;
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What [`clean_orphans`] does with an orphaned entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OrphanAction {
+    /// Remove the entry.
+    Remove,
+
+    /// Give the entry to another user or group, as
+    /// [`replace_principal`](crate::replace_principal) does.
+    ///
+    /// Only orphaned entries of the same kind are reassigned: a user takes
+    /// over orphaned user entries, and a group takes over orphaned group
+    /// entries. Other orphaned entries are left as they are.
+    Reassign(Principal),
+}
+
+/// Return the entries of a file's ACL whose uid, gid or GUID no longer
+/// belongs to an account.
+///
+/// On Linux and `FreeBSD`, this includes the default ACL of a directory.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn find_orphans<P: AsRef<Path>>(path: P) -> io::Result<Vec<AclEntry>> {
+    find_file(path.as_ref(), &NameCache::default())
+}
+
+/// Walk the tree at `root` and return the orphaned entries of each file that
+/// has any.
+///
+/// Symlinks are not followed.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn find_orphans_tree<P: AsRef<Path>>(root: P) -> io::Result<Vec<(PathBuf, Vec<AclEntry>)>> {
+    let names = NameCache::default();
+    let mut result = Vec::new();
+    for item in Walk::new(root.as_ref()) {
+        let entry = item?;
+        if entry.metadata.is_symlink() {
+            continue;
+        }
+
+        let orphans = find_file(&entry.path, &names)?;
+        if !orphans.is_empty() {
+            result.push((entry.path, orphans));
+        }
+    }
+    Ok(result)
+}
+
+/// Remove or reassign the orphaned entries of a file's ACL.
+///
+/// Returns the orphaned entries that were removed or reassigned, as they were
+/// before the change. The ACL is only written if there are any. The mask is
+/// left as it is.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::{clean_orphans, OrphanAction};
+///
+/// let replacement = "gid=100(archive)".parse()?;
+/// let orphans = clean_orphans("./tmp/foo", &OrphanAction::Reassign(replacement))?;
+/// for entry in &orphans {
+///     println!("reassigned {entry}");
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure, or if the replacement is not a user
+/// or group.
+pub fn clean_orphans<P: AsRef<Path>>(path: P, action: &OrphanAction) -> io::Result<Vec<AclEntry>> {
+    let replacement = replacement_key(action)?;
+    clean_file(path.as_ref(), replacement.as_ref(), &NameCache::default())
+}
+
+/// Walk the tree at `root` and remove or reassign the orphaned entries of
+/// every file.
+///
+/// Returns the removed or reassigned entries of each file that had any.
+/// Symlinks are not followed.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. Files before the failing one have
+/// already been cleaned.
+pub fn clean_orphans_tree<P: AsRef<Path>>(
+    root: P,
+    action: &OrphanAction,
+) -> io::Result<Vec<(PathBuf, Vec<AclEntry>)>> {
+    let replacement = replacement_key(action)?;
+    let names = NameCache::default();
+    let mut result = Vec::new();
+    for item in Walk::new(root.as_ref()) {
+        let entry = item?;
+        if entry.metadata.is_symlink() {
+            continue;
+        }
+
+        let orphans = clean_file(&entry.path, replacement.as_ref(), &names)?;
+        if !orphans.is_empty() {
+            result.push((entry.path, orphans));
+        }
+    }
+    Ok(result)
+}
+
+/// Return the orphaned entries of a file.
+fn find_file(path: &Path, names: &NameCache) -> io::Result<Vec<AclEntry>> {
+    let entries = read_entries(path, names)?;
+    Ok(entries
+        .into_iter()
+        .filter(|(_, orphan)| *orphan)
+        .map(|(entry, _)| entry)
+        .collect())
+}
+
+/// Remove the orphaned entries of a file, or give them to `replacement`.
+fn clean_file(
+    path: &Path,
+    replacement: Option<&(AclEntryKind, String)>,
+    names: &NameCache,
+) -> io::Result<Vec<AclEntry>> {
+    let (mut entries, orphan): (Vec<_>, Vec<_>) = read_entries(path, names)?.into_iter().unzip();
+    let mut orphans = Vec::new();
+
+    // Go backwards, so that removing an entry does not move the entries
+    // still to visit.
+    for index in (0..entries.len()).rev() {
+        if !orphan[index] {
+            continue;
+        }
+        match replacement {
+            Some(to) if to.0 == entries[index].kind => {
+                orphans.push(entries[index].clone());
+                reassign_entry(&mut entries, index, to);
+            }
+            Some(_) => {}
+            None => orphans.push(entries.remove(index)),
+        }
+    }
+    orphans.reverse();
+
+    if !orphans.is_empty() {
+        setfacl(&[path], &entries, None)?;
+    }
+
+    Ok(orphans)
+}
+
+/// Read the ACL of a file, and mark each entry that is orphaned.
+///
+/// The entries are read with their uid's and gid's, so names are only
+/// looked up once per id, through `names`.
+fn read_entries(path: &Path, names: &NameCache) -> io::Result<Vec<(AclEntry, bool)>> {
+    let mut acl = getfacl_raw(path, None)?;
+    let mut result = Vec::new();
+    for raw in acl.entries() {
+        let raw = raw?;
+        let orphan = is_orphan(raw.qualifier(), names)?;
+        result.push((raw.to_entry_cached(Some(names))?, orphan));
+    }
+    Ok(result)
+}
+
+/// Return the kind and name of the entries that replace orphaned ones.
+fn replacement_key(action: &OrphanAction) -> io::Result<Option<(AclEntryKind, String)>> {
+    let principal = match action {
+        OrphanAction::Remove => return Ok(None),
+        OrphanAction::Reassign(principal) => principal,
+    };
+
+    match entry_key(principal)? {
+        Some(key) => Ok(Some(key)),
+        None => fail_custom(&format!(
+            "invalid replacement principal: {:?}",
+            principal.to_string()
+        )),
+    }
+}
+
+/// Return true if the qualifier is a uid, gid or GUID without an account.
+fn is_orphan(qualifier: &Qualifier, names: &NameCache) -> io::Result<bool> {
+    let orphan = match qualifier {
+        Qualifier::User(_) | Qualifier::Group(_) => names.find_name(qualifier)?.is_none(),
+        #[cfg(target_os = "macos")]
+        Qualifier::Guid(_) => true,
+        _ => false,
+    };
+    Ok(orphan)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, target_os = "linux"))]
+mod orphan_tests {
+    use super::*;
+    use crate::{from_mode, getfacl, Flag, Perm};
+    use std::fs;
+
+    fn named_entries(flags: Flag) -> Vec<AclEntry> {
+        vec![
+            AclEntry::allow_user("root", Perm::READ, flags),
+            AclEntry::allow_user("1500", Perm::WRITE, flags),
+            AclEntry::allow_group("1501", Perm::READ, flags),
+            AclEntry::allow_mask(Perm::READ | Perm::WRITE, flags),
+        ]
+    }
+
+    #[test]
+    fn test_find_and_remove_orphans() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        fs::write(&file, "data")?;
+        let mut entries = from_mode(0o640);
+        entries.extend(named_entries(Flag::empty()));
+        entries[3].perms |= Perm::EXECUTE;
+        setfacl(&[&file], &entries, None)?;
+
+        let orphans = find_orphans(&file)?;
+        assert_eq!(orphans, vec![entries[4].clone(), entries[5].clone()]);
+
+        assert_eq!(clean_orphans(&file, &OrphanAction::Remove)?, orphans);
+        assert!(find_orphans(&file)?.is_empty());
+
+        // The mask is kept, so root does not gain the execute permission.
+        let result = getfacl(&file, None)?;
+        assert!(result.contains(&entries[3]));
+        assert!(result.contains(&entries[6]));
+
+        // Nothing left to clean.
+        assert!(clean_orphans(&file, &OrphanAction::Remove)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reassign_orphans_tree() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sub = dir.path().join("sub");
+        fs::create_dir(&sub)?;
+        let mut entries = from_mode(0o750);
+        entries.extend(named_entries(Flag::empty()));
+        entries.extend(from_mode(0o750).into_iter().map(|entry| AclEntry {
+            flags: Flag::DEFAULT,
+            ..entry
+        }));
+        entries.extend(named_entries(Flag::DEFAULT));
+        setfacl(&[&sub], &entries, None)?;
+
+        let found = find_orphans_tree(&dir)?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, sub);
+        assert_eq!(found[0].1.len(), 4);
+
+        // The uid is resolved to "root", which already has an entry.
+        let root = Principal::User { id: 0, name: None };
+        let cleaned = clean_orphans_tree(&dir, &OrphanAction::Reassign(root))?;
+        assert_eq!(
+            cleaned,
+            [(sub.clone(), vec![entries[4].clone(), entries[11].clone()])]
+        );
+
+        // The user orphans are merged into the existing root entries. The
+        // group orphans are left alone.
+        let result = getfacl(&sub, None)?;
+        assert!(result.contains(&AclEntry::allow_user(
+            "root",
+            Perm::READ | Perm::WRITE,
+            None
+        )));
+        assert!(result.contains(&AclEntry::allow_user(
+            "root",
+            Perm::READ | Perm::WRITE,
+            Flag::DEFAULT
+        )));
+        assert_eq!(
+            find_orphans_tree(&dir)?,
+            [(sub, vec![entries[5].clone(), entries[12].clone()])]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_replacement() {
+        let err = clean_orphans("/", &OrphanAction::Reassign(Principal::Owner)).unwrap_err();
+        assert_eq!(err.to_string(), "invalid replacement principal: \"owner\"");
+    }
+}
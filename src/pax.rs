@@ -0,0 +1,476 @@
+//! Implements the ACL records stored in tar PAX extended headers.
+//!
+//! GNU tar, star and bsdtar store ACL's as text in the PAX headers
+//! `SCHILY.acl.access` and `SCHILY.acl.default` (POSIX.1e) and
+//! `SCHILY.acl.ace` (NFSv4). Named entries carry the numeric uid/gid after
+//! the name, so an archive can be restored on a system where the name is
+//! unknown.
+
+use crate::aclentry::AclEntry;
+use crate::failx::{custom_err, fail_custom};
+use crate::portable::{PortableAclEntry, PortableAclEntryKind, PortableFlag, PortablePerm};
+use crate::unix;
+
+use std::io;
+
+/// PAX header key for the POSIX.1e access ACL.
+pub const PAX_ACL_ACCESS: &str = "SCHILY.acl.access";
+
+/// PAX header key for the POSIX.1e default ACL.
+pub const PAX_ACL_DEFAULT: &str = "SCHILY.acl.default";
+
+/// PAX header key for the NFSv4 ACL.
+pub const PAX_ACL_ACE: &str = "SCHILY.acl.ace";
+
+/// Letters used for NFSv4 permissions, in the order they are written.
+const ACE_PERMS: [(char, PortablePerm); 14] = [
+    ('r', PortablePerm::READ),
+    ('w', PortablePerm::WRITE),
+    ('x', PortablePerm::EXECUTE),
+    ('p', PortablePerm::APPEND),
+    ('D', PortablePerm::DELETE_CHILD),
+    ('d', PortablePerm::DELETE),
+    ('a', PortablePerm::READATTR),
+    ('A', PortablePerm::WRITEATTR),
+    ('R', PortablePerm::READEXTATTR),
+    ('W', PortablePerm::WRITEEXTATTR),
+    ('c', PortablePerm::READSECURITY),
+    ('C', PortablePerm::WRITESECURITY),
+    ('o', PortablePerm::CHOWN),
+    ('s', PortablePerm::SYNC),
+];
+
+/// Letters used for NFSv4 flags, in the order they are written. The audit
+/// flags `S` and `F` have no equivalent; they are ignored.
+const ACE_FLAGS: [(char, PortableFlag); 7] = [
+    ('f', PortableFlag::FILE_INHERIT),
+    ('d', PortableFlag::DIRECTORY_INHERIT),
+    ('i', PortableFlag::ONLY_INHERIT),
+    ('n', PortableFlag::LIMIT_INHERIT),
+    ('S', PortableFlag::empty()),
+    ('F', PortableFlag::empty()),
+    ('I', PortableFlag::INHERITED),
+];
+
+/// NFSv4 read and write permissions are separate bits on `FreeBSD`.
+#[cfg(target_os = "freebsd")]
+const ACE_READ_WRITE: [PortablePerm; 2] = [PortablePerm::READ_DATA, PortablePerm::WRITE_DATA];
+#[cfg(not(target_os = "freebsd"))]
+const ACE_READ_WRITE: [PortablePerm; 2] = [PortablePerm::READ, PortablePerm::WRITE];
+
+/// Encode ACL entries as PAX header records.
+///
+/// Returns a list of `(key, value)` pairs. POSIX.1e ACL's produce
+/// [`PAX_ACL_ACCESS`] and, if there are default entries,
+/// [`PAX_ACL_DEFAULT`]. NFSv4 ACL's, including every macOS ACL, produce
+/// [`PAX_ACL_ACE`]. An empty list of entries produces no records.
+///
+/// The numeric uid/gid of each named entry is appended as a hint, when the
+/// name can be looked up.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let entries = exacl::getfacl("./tmp/foo", None)?;
+/// for (key, value) in exacl::to_pax_headers(&entries)? {
+///     println!("{key}={value}");
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if an entry cannot be represented.
+pub fn to_pax_headers(entries: &[AclEntry]) -> io::Result<Vec<(&'static str, String)>> {
+    let entries = PortableAclEntry::from_native_entries(entries);
+    let mut headers = Vec::new();
+
+    if cfg!(target_os = "macos") || entries.iter().any(is_nfs4) {
+        if !entries.is_empty() {
+            headers.push((PAX_ACL_ACE, encode_ace(&entries)?));
+        }
+        return Ok(headers);
+    }
+
+    let (default, access): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|entry| entry.flags.contains(PortableFlag::DEFAULT));
+    if !access.is_empty() {
+        headers.push((PAX_ACL_ACCESS, encode_posix(&access)?));
+    }
+    if !default.is_empty() {
+        headers.push((PAX_ACL_DEFAULT, encode_posix(&default)?));
+    }
+
+    Ok(headers)
+}
+
+/// Decode ACL entries from PAX header records.
+///
+/// `headers` yields `(key, value)` pairs, such as the PAX extensions of a
+/// tar entry. Keys other than [`PAX_ACL_ACCESS`], [`PAX_ACL_DEFAULT`] and
+/// [`PAX_ACL_ACE`] are ignored. Entries from [`PAX_ACL_DEFAULT`] have the
+/// `DEFAULT` flag set.
+///
+/// If a name is not known on this system and the record has a numeric
+/// uid/gid hint, the entry uses the numeric id instead of the name.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a record is malformed, or if an entry is not
+/// supported on this platform.
+pub fn from_pax_headers<'a, I>(headers: I) -> io::Result<Vec<AclEntry>>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let mut entries = Vec::new();
+    for (key, value) in headers {
+        let decoded = match key {
+            PAX_ACL_ACCESS => decode_posix(value, false),
+            PAX_ACL_DEFAULT => decode_posix(value, true),
+            PAX_ACL_ACE => decode_ace(value),
+            _ => continue,
+        };
+        let decoded = decoded.and_then(|decoded| PortableAclEntry::to_native_entries(&decoded));
+        entries.extend(decoded.map_err(|err| custom_err(key, &err))?);
+    }
+
+    Ok(entries)
+}
+
+/// Return true if the entry can only be represented as an NFSv4 ACE.
+fn is_nfs4(entry: &PortableAclEntry) -> bool {
+    let posix_perms = PortablePerm::READ | PortablePerm::WRITE | PortablePerm::EXECUTE;
+    !entry.allow
+        || entry.kind == PortableAclEntryKind::Everyone
+        || !(entry.perms - posix_perms).is_empty()
+        || !(entry.flags - PortableFlag::DEFAULT).is_empty()
+}
+
+/// Return the numeric uid/gid of a named entry, if it can be looked up.
+fn id_hint(entry: &PortableAclEntry) -> Option<u32> {
+    if entry.name.is_empty() {
+        return None;
+    }
+    match entry.kind {
+        PortableAclEntryKind::User => unix::name_to_uid(&entry.name).ok(),
+        PortableAclEntryKind::Group => unix::name_to_gid(&entry.name).ok(),
+        _ => None,
+    }
+}
+
+/// Return the name to use for a decoded entry.
+///
+/// Prefer the name if it is known on this system; otherwise, use the
+/// numeric id hint. An entry without a name, such as the file owner, keeps
+/// its empty name.
+fn resolve_name(kind: PortableAclEntryKind, name: &str, id: Option<&str>) -> io::Result<String> {
+    let Some(id) = id else {
+        return Ok(name.to_string());
+    };
+    if id.parse::<u32>().is_err() {
+        return fail_custom(&format!("invalid id: {id:?}"));
+    }
+
+    let known = name.is_empty()
+        || match kind {
+            PortableAclEntryKind::User => unix::name_to_uid(name).is_ok(),
+            PortableAclEntryKind::Group => unix::name_to_gid(name).is_ok(),
+            _ => true,
+        };
+    Ok(if known { name } else { id }.to_string())
+}
+
+/// Append ":id" to a record if the entry has an id hint.
+fn push_id(record: &mut String, entry: &PortableAclEntry) {
+    if let Some(id) = id_hint(entry) {
+        record.push(':');
+        record.push_str(&id.to_string());
+    }
+}
+
+/// Encode POSIX.1e entries, such as "user::rw-,user:bob:r--:1001,...".
+fn encode_posix(entries: &[PortableAclEntry]) -> io::Result<String> {
+    let mut records = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let tag = match entry.kind {
+            PortableAclEntryKind::User => "user",
+            PortableAclEntryKind::Group => "group",
+            PortableAclEntryKind::Mask => "mask",
+            PortableAclEntryKind::Other => "other",
+            kind => return fail_custom(&format!("unsupported kind `{kind}` in POSIX ACL")),
+        };
+        let perms = [
+            (PortablePerm::READ, 'r'),
+            (PortablePerm::WRITE, 'w'),
+            (PortablePerm::EXECUTE, 'x'),
+        ]
+        .iter()
+        .map(|(perm, ch)| {
+            if entry.perms.contains(*perm) {
+                *ch
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+
+        let mut record = format!("{tag}:{}:{perms}", entry.name);
+        push_id(&mut record, entry);
+        records.push(record);
+    }
+
+    Ok(records.join(","))
+}
+
+/// Decode POSIX.1e entries.
+fn decode_posix(text: &str, default: bool) -> io::Result<Vec<PortableAclEntry>> {
+    let flags = if default {
+        PortableFlag::DEFAULT
+    } else {
+        PortableFlag::empty()
+    };
+
+    let mut entries = Vec::new();
+    for record in text.split([',', '\n']).map(str::trim) {
+        if record.is_empty() {
+            continue;
+        }
+        let fields = record.split(':').collect::<Vec<_>>();
+        let (tag, name, perms, id) = match fields[..] {
+            [tag, name, perms] => (tag, name, perms, None),
+            [tag, name, perms, id] => (tag, name, perms, Some(id)),
+            _ => return fail_custom(&format!("invalid ACL record: {record:?}")),
+        };
+
+        let kind = match tag {
+            "user" | "u" => PortableAclEntryKind::User,
+            "group" | "g" => PortableAclEntryKind::Group,
+            "mask" | "m" => PortableAclEntryKind::Mask,
+            "other" | "o" => PortableAclEntryKind::Other,
+            _ => return fail_custom(&format!("unknown tag: {tag:?}")),
+        };
+
+        let mut bits = PortablePerm::empty();
+        for ch in perms.chars() {
+            bits |= match ch {
+                'r' => PortablePerm::READ,
+                'w' => PortablePerm::WRITE,
+                'x' => PortablePerm::EXECUTE,
+                '-' => PortablePerm::empty(),
+                _ => return fail_custom(&format!("unknown permission: {ch:?}")),
+            };
+        }
+
+        let name = resolve_name(kind, name, id)?;
+        entries.push(PortableAclEntry::new(kind, &name, bits, flags, true));
+    }
+
+    Ok(entries)
+}
+
+/// Encode NFSv4 entries, such as "owner@:rwxp--aARWcCos:-------:allow,...".
+fn encode_ace(entries: &[PortableAclEntry]) -> io::Result<String> {
+    let mut records = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let tag = match (entry.kind, entry.name.is_empty()) {
+            (PortableAclEntryKind::User, true) => "owner@".to_string(),
+            (PortableAclEntryKind::Group, true) => "group@".to_string(),
+            (PortableAclEntryKind::Everyone, _) => "everyone@".to_string(),
+            (PortableAclEntryKind::User, false) => format!("user:{}", entry.name),
+            (PortableAclEntryKind::Group, false) => format!("group:{}", entry.name),
+            (kind, _) => return fail_custom(&format!("unsupported kind `{kind}` in NFSv4 ACL")),
+        };
+
+        let perms = ACE_PERMS
+            .iter()
+            .map(|(ch, perm)| {
+                let perm = match *ch {
+                    'r' => *perm | PortablePerm::READ_DATA,
+                    'w' => *perm | PortablePerm::WRITE_DATA,
+                    _ => *perm,
+                };
+                if entry.perms.intersects(perm) {
+                    *ch
+                } else {
+                    '-'
+                }
+            })
+            .collect::<String>();
+        let flags = ACE_FLAGS
+            .iter()
+            .map(|(ch, flag)| {
+                if !flag.is_empty() && entry.flags.contains(*flag) {
+                    *ch
+                } else {
+                    '-'
+                }
+            })
+            .collect::<String>();
+        let kind = if entry.allow { "allow" } else { "deny" };
+
+        let mut record = format!("{tag}:{perms}:{flags}:{kind}");
+        push_id(&mut record, entry);
+        records.push(record);
+    }
+
+    Ok(records.join(","))
+}
+
+/// Decode NFSv4 entries.
+fn decode_ace(text: &str) -> io::Result<Vec<PortableAclEntry>> {
+    let mut entries = Vec::new();
+    for record in text.split([',', '\n']).map(str::trim) {
+        if record.is_empty() {
+            continue;
+        }
+        let fields = record.split(':').collect::<Vec<_>>();
+        let (kind, name, rest) = match fields[..] {
+            ["owner@", ref rest @ ..] => (PortableAclEntryKind::User, "", rest),
+            ["group@", ref rest @ ..] => (PortableAclEntryKind::Group, "", rest),
+            ["everyone@", ref rest @ ..] => (PortableAclEntryKind::Everyone, "", rest),
+            ["user" | "u", name, ref rest @ ..] => (PortableAclEntryKind::User, name, rest),
+            ["group" | "g", name, ref rest @ ..] => (PortableAclEntryKind::Group, name, rest),
+            _ => return fail_custom(&format!("invalid ACL record: {record:?}")),
+        };
+        let (perms, flags, allow, id) = match rest {
+            [perms, flags, allow] => (*perms, *flags, *allow, None),
+            [perms, flags, allow, id] => (*perms, *flags, *allow, Some(*id)),
+            _ => return fail_custom(&format!("invalid ACL record: {record:?}")),
+        };
+
+        let mut perm_bits = PortablePerm::empty();
+        for ch in perms.chars().filter(|ch| *ch != '-') {
+            perm_bits |= match ch {
+                'r' => ACE_READ_WRITE[0],
+                'w' => ACE_READ_WRITE[1],
+                _ => match ACE_PERMS.iter().find(|item| item.0 == ch) {
+                    Some((_, perm)) => *perm,
+                    None => return fail_custom(&format!("unknown permission: {ch:?}")),
+                },
+            };
+        }
+
+        let mut flag_bits = PortableFlag::empty();
+        for ch in flags.chars().filter(|ch| *ch != '-') {
+            match ACE_FLAGS.iter().find(|item| item.0 == ch) {
+                Some((_, flag)) => flag_bits |= *flag,
+                None => return fail_custom(&format!("unknown flag: {ch:?}")),
+            }
+        }
+
+        let allow = match allow {
+            "allow" => true,
+            "deny" => false,
+            _ => return fail_custom(&format!("unsupported entry type: {allow:?}")),
+        };
+
+        let name = resolve_name(kind, name, id)?;
+        entries.push(PortableAclEntry::new(
+            kind, &name, perm_bits, flag_bits, allow,
+        ));
+    }
+
+    Ok(entries)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod pax_tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_ace() -> io::Result<()> {
+        let text = "owner@:rwxp--aARWcCos:-------:allow,\
+                    user:non_existent_user_5f2a:r-----a-R-c---:fd----I:deny:1001,\
+                    everyone@:r-----a-R-c--s:-------:allow";
+        let entries = decode_ace(text)?;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].kind, PortableAclEntryKind::User);
+        assert_eq!(entries[0].name, "");
+        assert!(entries[0]
+            .perms
+            .contains(PortablePerm::APPEND | PortablePerm::SYNC));
+        assert_eq!(entries[1].name, "1001");
+        assert!(!entries[1].allow);
+        assert_eq!(
+            entries[1].flags,
+            PortableFlag::FILE_INHERIT | PortableFlag::DIRECTORY_INHERIT | PortableFlag::INHERITED
+        );
+        assert_eq!(entries[2].kind, PortableAclEntryKind::Everyone);
+
+        assert_eq!(
+            encode_ace(&entries[..1])?,
+            "owner@:rwxp--aARWcCos:-------:allow"
+        );
+        assert_eq!(
+            encode_ace(&entries[1..2])?,
+            "user:1001:r-----a-R-c---:fd----I:deny:1001"
+        );
+
+        for text in ["owner@:rwx:---", "user:rwx:---:allow", "owner@:q:-:allow"] {
+            assert!(decode_ace(text).is_err(), "{text}");
+        }
+        assert_eq!(
+            decode_ace("owner@:r:-:audit").unwrap_err().to_string(),
+            "unsupported entry type: \"audit\""
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_pax_headers() -> io::Result<()> {
+        use crate::{from_mode, AclEntry, Flag, Perm};
+
+        let mut entries = from_mode(0o750);
+        entries.push(AclEntry::allow_user("500", Perm::READ, None));
+        entries.push(AclEntry::allow_mask(Perm::READ, None));
+        entries.push(AclEntry::allow_group("", Perm::READ, Flag::DEFAULT));
+
+        let headers = to_pax_headers(&entries)?;
+        assert_eq!(
+            headers,
+            [
+                (
+                    PAX_ACL_ACCESS,
+                    "user::rwx,group::r-x,other::---,user:500:r--:500,mask::r--".to_string()
+                ),
+                (PAX_ACL_DEFAULT, "group::r--".to_string()),
+            ]
+        );
+
+        let decoded = from_pax_headers(headers.iter().map(|(key, value)| (*key, value.as_str())))?;
+        assert_eq!(decoded, entries);
+
+        // An unknown name falls back to the id hint. Other keys are ignored.
+        let decoded = from_pax_headers([
+            ("path", "foo"),
+            (PAX_ACL_ACCESS, "u:non_existent_user_5f2a:rw-:1001"),
+        ])?;
+        assert_eq!(
+            decoded,
+            [AclEntry::allow_user("1001", Perm::READ | Perm::WRITE, None)]
+        );
+
+        // The id hint does not turn the owner entry into a named entry.
+        let decoded = from_pax_headers([(PAX_ACL_ACCESS, "user::rwx:1000")])?;
+        assert_eq!(decoded, [entries[0].clone()]);
+
+        let err = from_pax_headers([(PAX_ACL_ACCESS, "user::rwx:abc")]).unwrap_err();
+        assert_eq!(err.to_string(), "SCHILY.acl.access: invalid id: \"abc\"");
+        assert!(to_pax_headers(&[])?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_pax_ace_unsupported() {
+        let err = from_pax_headers([(PAX_ACL_ACE, "everyone@:r:-:allow")]).unwrap_err();
+        assert!(err.to_string().starts_with("SCHILY.acl.ace: entry 0: "));
+    }
+}
@@ -130,6 +130,60 @@ impl Perm {
     }
 }
 
+impl Perm {
+    /// Return the permissions for one octal digit of a file mode.
+    ///
+    /// 4 is [`Perm::READ`], 2 is [`Perm::WRITE`] and 1 is [`Perm::EXECUTE`].
+    /// Bits outside the mask 0o7 are ignored.
+    #[must_use]
+    pub fn from_mode_bits(bits: u32) -> Perm {
+        let mut perms = Perm::empty();
+        perms.set(Perm::READ, bits & 0o4 != 0);
+        perms.set(Perm::WRITE, bits & 0o2 != 0);
+        perms.set(Perm::EXECUTE, bits & 0o1 != 0);
+        perms
+    }
+
+    /// Return the octal digit of a file mode for these permissions.
+    ///
+    /// Permissions other than read, write and execute are ignored.
+    #[must_use]
+    pub fn to_mode_bits(self) -> u32 {
+        u32::from(self.contains(Perm::READ)) << 2
+            | u32::from(self.contains(Perm::WRITE)) << 1
+            | u32::from(self.contains(Perm::EXECUTE))
+    }
+
+    /// Return the owner, group and other permissions in a file mode.
+    ///
+    /// Bits outside the mask 0o777 are ignored.
+    #[must_use]
+    pub fn from_mode(mode: u32) -> [Perm; 3] {
+        [
+            Perm::from_mode_bits(mode >> 6),
+            Perm::from_mode_bits(mode >> 3),
+            Perm::from_mode_bits(mode),
+        ]
+    }
+
+    /// Return the file mode for owner, group and other permissions.
+    #[must_use]
+    pub fn to_mode(perms: [Perm; 3]) -> u32 {
+        perms[0].to_mode_bits() << 6 | perms[1].to_mode_bits() << 3 | perms[2].to_mode_bits()
+    }
+
+    /// Return the permissions as a "rwx" string, such as "r-x".
+    ///
+    /// Permissions other than read, write and execute are ignored.
+    #[must_use]
+    pub fn to_rwx_string(self) -> String {
+        [(Perm::READ, 'r'), (Perm::WRITE, 'w'), (Perm::EXECUTE, 'x')]
+            .iter()
+            .map(|(perm, ch)| if self.contains(*perm) { *ch } else { '-' })
+            .collect()
+    }
+}
+
 impl BitIterable for Perm {
     fn lsb(self) -> Option<Self> {
         if self.is_empty() {
@@ -283,6 +337,14 @@ impl fmt::Display for Perm {
     }
 }
 
+/// Parse a single octal digit, "0" to "7".
+fn parse_perm_digit(s: &str) -> Option<Perm> {
+    match s.as_bytes() {
+        [digit @ b'0'..=b'7'] => Some(Perm::from_mode_bits(u32::from(digit - b'0'))),
+        _ => None,
+    }
+}
+
 /// Parse an abbreviated permission, "rwx", "wx", "r-x" etc.
 ///
 /// Order doesn't matter. "xwr" is the same as "rwx". Allow for "r-x" by
@@ -319,7 +381,9 @@ impl std::str::FromStr for Perm {
         for item in s.split(',') {
             let word = item.trim();
             if !word.is_empty() {
-                if let Some(perms) = parse_perm_abbreviation(word) {
+                if let Some(perms) =
+                    parse_perm_digit(word).or_else(|| parse_perm_abbreviation(word))
+                {
                     result |= perms;
                 } else {
                     result |= word.parse::<PermName>()?.to_perm();
@@ -428,8 +492,13 @@ mod perm_tests {
         assert_eq!(Perm::WRITE, "w".parse().unwrap());
         assert_eq!(Perm::empty(), "".parse().unwrap());
 
+        assert_eq!(flags, "5".parse().unwrap());
+        assert_eq!(Perm::empty(), "0".parse().unwrap());
+
         // Duplicate abbreviations not supported.
         assert!("rr".parse::<Perm>().is_err());
+        assert!("8".parse::<Perm>().is_err());
+        assert!("55".parse::<Perm>().is_err());
 
         #[cfg(target_os = "macos")]
         {
@@ -459,6 +528,32 @@ mod perm_tests {
         }
     }
 
+    #[test]
+    fn test_perm_mode_bits() {
+        let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+        assert_eq!(Perm::from_mode_bits(0o7), rwx);
+        assert_eq!(Perm::from_mode_bits(0o4), Perm::READ);
+        assert_eq!(Perm::from_mode_bits(0o10), Perm::empty());
+        assert_eq!(rwx.to_rwx_string(), "rwx");
+        assert_eq!((Perm::READ | Perm::EXECUTE).to_rwx_string(), "r-x");
+        assert_eq!(Perm::empty().to_rwx_string(), "---");
+
+        for bits in 0..8 {
+            let perms = Perm::from_mode_bits(bits);
+            assert_eq!(perms.to_mode_bits(), bits);
+            assert_eq!(perms.to_rwx_string().parse::<Perm>().unwrap(), perms);
+            assert_eq!(bits.to_string().parse::<Perm>().unwrap(), perms);
+        }
+
+        assert_eq!(
+            Perm::from_mode(0o1750),
+            [rwx, Perm::READ | Perm::EXECUTE, Perm::empty()]
+        );
+        for mode in 0..0o1000 {
+            assert_eq!(Perm::to_mode(Perm::from_mode(mode)), mode);
+        }
+    }
+
     #[test]
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
     fn test_perm_unix_permission() {
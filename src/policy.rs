@@ -0,0 +1,691 @@
+//! Implements a declarative ACL policy with check and apply modes.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::diff::{compare_keys, is_default};
+use crate::format;
+#[cfg(feature = "serde")]
+use crate::journal::Journal;
+use crate::perm::Perm;
+use crate::walk::Walk;
+use crate::{getfacl, setfacl};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Rule of an [`AclPolicy`] that applies to paths matching a pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyRule {
+    /// Glob pattern for the paths this rule applies to.
+    pub pattern: String,
+
+    /// Entries that matching paths must have. Each entry must be present
+    /// with at least the given permissions. Default entries only apply to
+    /// directories.
+    pub require: Vec<AclEntry>,
+
+    /// Permissions that no entry may allow, beyond the permissions a required
+    /// entry needs. The file owner and mask entries are exempt.
+    pub restrict: Perm,
+}
+
+impl PolicyRule {
+    /// Return true if this rule applies to `path`.
+    #[must_use]
+    pub fn matches(&self, path: &Path) -> bool {
+        glob_match(self.pattern.as_bytes(), path.as_os_str().as_bytes())
+    }
+}
+
+/// Desired-state ACL policy.
+///
+/// A policy is a list of rules. Each rule applies to the paths that match
+/// its glob pattern, and lists the entries those paths must have and the
+/// permissions no other entry may allow. When several rules match a path,
+/// all of them apply.
+///
+/// In a pattern, `*` matches any part of a path component, `?` matches any
+/// one character except `/` and `**` matches any number of components. A
+/// pattern ending in `/**` also matches the directory itself. Patterns are
+/// matched against paths as they are formed from the root passed to
+/// [`AclPolicy::check`] or [`AclPolicy::apply`].
+///
+/// # Text Format
+///
+/// Each rule begins with its pattern in square brackets, followed by
+/// `require` lines with an entry in the format accepted by
+/// [`from_reader`](crate::from_reader), and `restrict` lines with a
+/// comma-separated list of permissions. A comment begins with `#`.
+///
+/// ```text
+/// [/srv/shared/**]
+/// require group:eng:rwx
+/// require default:group:eng:rwx
+/// restrict write    # nobody else may write
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AclPolicy {
+    /// Rules of the policy, in order.
+    pub rules: Vec<PolicyRule>,
+}
+
+/// Kind of policy violation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ViolationKind {
+    /// A required entry is missing or lacks some of the required permissions.
+    Missing(AclEntry),
+
+    /// An entry allows restricted permissions.
+    Excess {
+        /// The offending entry.
+        entry: AclEntry,
+        /// The restricted permissions it allows.
+        perms: Perm,
+    },
+}
+
+/// Violation of an [`AclPolicy`] found at a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct PolicyViolation {
+    /// Path of the file.
+    pub path: PathBuf,
+
+    /// What is wrong with the file's ACL.
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ViolationKind::Missing(entry) => {
+                write!(f, "{}: missing {}", self.path.display(), entry)
+            }
+            ViolationKind::Excess { entry, perms } => {
+                write!(f, "{}: {} allows {}", self.path.display(), entry, perms)
+            }
+        }
+    }
+}
+
+impl AclPolicy {
+    /// Check the ACL entries of a single file against the policy.
+    ///
+    /// `is_dir` determines whether default entries are required.
+    #[must_use]
+    pub fn check_entries(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        entries: &[AclEntry],
+    ) -> Vec<PolicyViolation> {
+        let mut result = Vec::new();
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(path))
+            .collect::<Vec<_>>();
+
+        let required = rules
+            .iter()
+            .flat_map(|rule| &rule.require)
+            .filter(|req| is_dir || !is_default(req))
+            .collect::<Vec<_>>();
+        let restrict = rules
+            .iter()
+            .fold(Perm::empty(), |perms, rule| perms | rule.restrict);
+
+        for req in &required {
+            let found = entries
+                .iter()
+                .find(|entry| compare_keys(entry, req) == Ordering::Equal);
+            let satisfied =
+                found.is_some_and(|entry| effective_perms(entry, entries).contains(req.perms));
+            if !satisfied {
+                result.push(PolicyViolation {
+                    path: path.to_path_buf(),
+                    kind: ViolationKind::Missing((*req).clone()),
+                });
+            }
+        }
+
+        for entry in entries {
+            if !entry.allow || is_exempt(entry) {
+                continue;
+            }
+
+            // A required entry may keep the permissions it is required to have.
+            let needed = required
+                .iter()
+                .filter(|req| compare_keys(entry, req) == Ordering::Equal)
+                .fold(Perm::empty(), |perms, req| perms | req.perms);
+
+            let perms = (effective_perms(entry, entries) & restrict) - needed;
+            if !perms.is_empty() {
+                result.push(PolicyViolation {
+                    path: path.to_path_buf(),
+                    kind: ViolationKind::Excess {
+                        entry: entry.clone(),
+                        perms,
+                    },
+                });
+            }
+        }
+
+        result
+    }
+
+    /// Walk the tree at `root` and report every violation of the policy.
+    ///
+    /// Symlinks are not followed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn check<P: AsRef<Path>>(&self, root: P) -> io::Result<Vec<PolicyViolation>> {
+        let mut result = Vec::new();
+        self.visit(root.as_ref(), |_, _, _, violations| {
+            result.extend(violations);
+            Ok(())
+        })?;
+        Ok(result)
+    }
+
+    /// Walk the tree at `root` and correct every violation of the policy.
+    ///
+    /// Missing entries are added, entries with too few permissions are
+    /// extended and restricted permissions are removed from other entries.
+    /// On Linux, the existing mask is kept, and only gains the permissions a
+    /// required entry needs. If that exposes restricted permissions of other
+    /// entries, they are removed too. Symlinks are not followed.
+    ///
+    /// Returns the violations that were corrected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure. Files before the failing one have
+    /// already been corrected.
+    pub fn apply<P: AsRef<Path>>(&self, root: P) -> io::Result<Vec<PolicyViolation>> {
+        self.apply_impl(root.as_ref(), |_| Ok(()))
+    }
+
+    /// Same as [`AclPolicy::apply`], but record the previous ACL of each file
+    /// in `journal` before it is corrected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    #[cfg(feature = "serde")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub fn apply_with_journal<P, W>(
+        &self,
+        root: P,
+        journal: &mut Journal<W>,
+    ) -> io::Result<Vec<PolicyViolation>>
+    where
+        P: AsRef<Path>,
+        W: io::Write,
+    {
+        self.apply_impl(root.as_ref(), |path| journal.record(path, None))
+    }
+
+    /// Correct every violation, calling `before_write` before each change.
+    fn apply_impl<F>(&self, root: &Path, mut before_write: F) -> io::Result<Vec<PolicyViolation>>
+    where
+        F: FnMut(&Path) -> io::Result<()>,
+    {
+        let mut result = Vec::new();
+        self.visit(root, |path, is_dir, entries, mut violations| {
+            let corrected = self.correct(path, is_dir, entries, &mut violations);
+            before_write(path)?;
+            setfacl(&[path], &corrected, None)?;
+            result.extend(violations);
+            Ok(())
+        })?;
+        Ok(result)
+    }
+
+    /// Return corrected entries for a file.
+    ///
+    /// Adding permissions to the mask for a required entry may expose
+    /// restricted permissions of other entries, so the corrected entries are
+    /// checked again. Violations found then are appended to `violations`.
+    fn correct(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        entries: Vec<AclEntry>,
+        violations: &mut Vec<PolicyViolation>,
+    ) -> Vec<AclEntry> {
+        let corrected = correct_entries(entries, violations);
+        let exposed = self.check_entries(path, is_dir, &corrected);
+        let corrected = correct_entries(corrected, &exposed);
+        violations.extend(exposed);
+        corrected
+    }
+
+    /// Walk the tree and call `func` for each file with violations.
+    fn visit<F>(&self, root: &Path, mut func: F) -> io::Result<()>
+    where
+        F: FnMut(&Path, bool, Vec<AclEntry>, Vec<PolicyViolation>) -> io::Result<()>,
+    {
+        for item in Walk::new(root) {
+            let entry = item?;
+            if entry.metadata.is_symlink()
+                || !self.rules.iter().any(|rule| rule.matches(&entry.path))
+            {
+                continue;
+            }
+
+            let entries = getfacl(&entry.path, None)?;
+            let is_dir = entry.metadata.is_dir();
+            let violations = self.check_entries(&entry.path, is_dir, &entries);
+            if !violations.is_empty() {
+                func(&entry.path, is_dir, entries, violations)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return corrected entries that resolve the given violations.
+///
+/// The mask is kept. A required entry that is limited by the mask adds its
+/// permissions to the mask.
+fn correct_entries(entries: Vec<AclEntry>, violations: &[PolicyViolation]) -> Vec<AclEntry> {
+    let mut result = entries;
+
+    for violation in violations {
+        match &violation.kind {
+            ViolationKind::Missing(req) => {
+                match result
+                    .iter_mut()
+                    .find(|entry| compare_keys(entry, req) == Ordering::Equal)
+                {
+                    Some(entry) => entry.perms |= req.perms,
+                    None => result.push(req.clone()),
+                }
+                widen_mask(&mut result, req);
+            }
+            ViolationKind::Excess { entry, perms } => {
+                for item in result
+                    .iter_mut()
+                    .filter(|item| compare_keys(item, entry) == Ordering::Equal)
+                {
+                    item.perms -= *perms;
+                }
+            }
+        }
+    }
+
+    fill_default_entries(&mut result);
+    result
+}
+
+/// Return true if the entry is exempt from restricted permissions.
+fn is_exempt(entry: &AclEntry) -> bool {
+    match entry.kind {
+        AclEntryKind::User => entry.name.is_empty(),
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        AclEntryKind::Mask => true,
+        _ => false,
+    }
+}
+
+/// Return true if the entry's permissions are limited by the mask.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn is_masked(entry: &AclEntry) -> bool {
+    match entry.kind {
+        AclEntryKind::User => !entry.name.is_empty(),
+        AclEntryKind::Group => true,
+        _ => false,
+    }
+}
+
+/// Return the permissions of an entry, limited by the mask if there is one.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+pub(crate) fn effective_perms(entry: &AclEntry, entries: &[AclEntry]) -> Perm {
+    let mask = entries
+        .iter()
+        .find(|item| item.kind == AclEntryKind::Mask && is_default(item) == is_default(entry));
+
+    match mask {
+        Some(mask) if is_masked(entry) => entry.perms & mask.perms,
+        _ => entry.perms,
+    }
+}
+
+/// Return the permissions of an entry, limited by the mask if there is one.
+#[cfg(target_os = "macos")]
+pub(crate) fn effective_perms(entry: &AclEntry, _entries: &[AclEntry]) -> Perm {
+    entry.perms
+}
+
+/// Add the permissions of a required entry to the mask that limits it.
+///
+/// Without a mask entry, `setfacl` computes the mask.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn widen_mask(entries: &mut [AclEntry], req: &AclEntry) {
+    if !is_masked(req) {
+        return;
+    }
+    if let Some(mask) = entries
+        .iter_mut()
+        .find(|item| item.kind == AclEntryKind::Mask && is_default(item) == is_default(req))
+    {
+        mask.perms |= req.perms;
+    }
+}
+
+/// Add the permissions of a required entry to the mask that limits it.
+#[cfg(target_os = "macos")]
+fn widen_mask(_entries: &mut [AclEntry], _req: &AclEntry) {}
+
+/// Copy the owner, group owner and other entries of the access ACL to the
+/// default ACL if they are missing there, like setfacl(1) does.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn fill_default_entries(entries: &mut Vec<AclEntry>) {
+    if !entries.iter().any(is_default) {
+        return;
+    }
+
+    for kind in [AclEntryKind::User, AclEntryKind::Group, AclEntryKind::Other] {
+        let is_base = |entry: &&AclEntry| entry.kind == kind && entry.name.is_empty();
+        if entries.iter().filter(is_base).any(is_default) {
+            continue;
+        }
+        if let Some(base) = entries
+            .iter()
+            .filter(is_base)
+            .find(|entry| !is_default(entry))
+        {
+            let mut entry = base.clone();
+            entry.flags |= crate::Flag::DEFAULT;
+            entries.push(entry);
+        }
+    }
+}
+
+/// Copy the owner, group owner and other entries of the access ACL to the
+/// default ACL if they are missing there, like setfacl(1) does.
+#[cfg(target_os = "macos")]
+fn fill_default_entries(_entries: &mut Vec<AclEntry>) {}
+
+/// Match a path against a glob pattern.
+fn glob_match(pattern: &[u8], path: &[u8]) -> bool {
+    if pattern == b"/**" && path.is_empty() {
+        return true;
+    }
+
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+            if rest.is_empty() {
+                return true;
+            }
+            glob_match(rest, path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .any(|(i, ch)| *ch == b'/' && glob_match(rest, &path[i + 1..]))
+        }
+        [b'*', rest @ ..] => (0..=path.len())
+            .take_while(|i| *i == 0 || path[i - 1] != b'/')
+            .any(|i| glob_match(rest, &path[i..])),
+        [b'?', rest @ ..] => match path {
+            [ch, tail @ ..] => *ch != b'/' && glob_match(rest, tail),
+            [] => false,
+        },
+        [expected, rest @ ..] => match path {
+            [ch, tail @ ..] => ch == expected && glob_match(rest, tail),
+            [] => false,
+        },
+    }
+}
+
+impl std::str::FromStr for AclPolicy {
+    type Err = format::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = AclPolicy::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.find('#').map_or(line, |n| &line[0..n]).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let err = |msg: String| format::Error::Message(format!("line {}: {msg}", i + 1));
+
+            if let Some(pattern) = line.strip_prefix('[') {
+                let Some(pattern) = pattern.strip_suffix(']') else {
+                    return Err(err(format!("expected `]`: `{line}`")));
+                };
+                policy.rules.push(PolicyRule {
+                    pattern: pattern.trim().to_string(),
+                    require: Vec::new(),
+                    restrict: Perm::empty(),
+                });
+                continue;
+            }
+
+            let Some(rule) = policy.rules.last_mut() else {
+                return Err(err(format!("expected `[pattern]`: `{line}`")));
+            };
+
+            let (keyword, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match keyword {
+                "require" => rule
+                    .require
+                    .push(value.trim().parse().map_err(|e| err(format!("{e}")))?),
+                "restrict" => {
+                    rule.restrict |= value
+                        .trim()
+                        .parse::<Perm>()
+                        .map_err(|e| err(format!("{e}")))?;
+                }
+                _ => return Err(err(format!("unknown keyword `{keyword}`"))),
+            }
+        }
+
+        Ok(policy)
+    }
+}
+
+impl fmt::Display for AclPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", rule.pattern)?;
+            for entry in &rule.require {
+                writeln!(f, "require {entry}")?;
+            }
+            if !rule.restrict.is_empty() {
+                writeln!(f, "restrict {}", rule.restrict)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod policy_tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        let cases = [
+            ("/srv/shared/**", "/srv/shared", true),
+            ("/srv/shared/**", "/srv/shared/a/b", true),
+            ("/srv/shared/**", "/srv/sharedx", false),
+            ("/srv/*/data", "/srv/x/data", true),
+            ("/srv/*/data", "/srv/x/y/data", false),
+            ("/srv/**/data", "/srv/data", true),
+            ("/srv/**/data", "/srv/x/y/data", true),
+            ("*.txt", "a.txt", true),
+            ("*.txt", "d/a.txt", false),
+            ("**/*.txt", "d/a.txt", true),
+            ("a?c", "abc", true),
+            ("a?c", "a/c", false),
+        ];
+
+        for (pattern, path, expected) in cases {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), path.as_bytes()),
+                expected,
+                "{pattern} {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_policy_fromstr() {
+        let text = "# comment\n[/srv/**]\nrequire group:eng:rwx\nrestrict write # no\n";
+        let policy = text.parse::<AclPolicy>().unwrap();
+        assert_eq!(
+            policy.rules,
+            [PolicyRule {
+                pattern: String::from("/srv/**"),
+                require: vec![AclEntry::allow_group("eng", Perm::all(), None)],
+                restrict: Perm::WRITE,
+            }]
+        );
+        assert_eq!(
+            policy.to_string(),
+            "[/srv/**]\nrequire allow::group:eng:read,write,execute\nrestrict write\n"
+        );
+        assert_eq!(policy.to_string().parse::<AclPolicy>().unwrap(), policy);
+
+        let err = "require user:a:r".parse::<AclPolicy>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: expected `[pattern]`: `require user:a:r`"
+        );
+
+        let err = "[x]\nallow user:a:r".parse::<AclPolicy>().unwrap_err();
+        assert_eq!(err.to_string(), "line 2: unknown keyword `allow`");
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_policy_check_entries() {
+        use crate::Flag;
+
+        let policy = "[/srv/**]\nrequire group:eng:rwx\nrequire d:group:eng:rwx\nrestrict w"
+            .parse::<AclPolicy>()
+            .unwrap();
+
+        let entries = vec![
+            AclEntry::allow_user("", Perm::all(), None),
+            AclEntry::allow_group("", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_group("eng", Perm::all(), None),
+            AclEntry::allow_mask(Perm::READ | Perm::EXECUTE, None),
+            AclEntry::allow_other(Perm::READ, None),
+        ];
+
+        assert!(policy
+            .check_entries(Path::new("/tmp/x"), true, &entries)
+            .is_empty());
+
+        let violations = policy.check_entries(Path::new("/srv/x"), false, &entries);
+        let messages = violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        // The mask limits the group owner to read and execute.
+        assert_eq!(
+            messages,
+            ["/srv/x: missing allow::group:eng:read,write,execute"]
+        );
+
+        let mut violations = policy.check_entries(Path::new("/srv"), true, &entries);
+        assert_eq!(violations.len(), 2);
+
+        // Adding write to the mask for `eng` exposes write for the group owner,
+        // so it is removed.
+        let corrected = policy.correct(Path::new("/srv"), true, entries, &mut violations);
+        assert_eq!(violations.len(), 4);
+        assert_eq!(
+            violations[2].to_string(),
+            "/srv: allow::group::read,write allows write"
+        );
+        assert_eq!(
+            corrected,
+            [
+                AclEntry::allow_user("", Perm::all(), None),
+                AclEntry::allow_group("", Perm::READ, None),
+                AclEntry::allow_group("eng", Perm::all(), None),
+                AclEntry::allow_mask(Perm::all(), None),
+                AclEntry::allow_other(Perm::READ, None),
+                AclEntry::allow_group("eng", Perm::all(), Flag::DEFAULT),
+                AclEntry::allow_user("", Perm::all(), Flag::DEFAULT),
+                AclEntry::allow_group("", Perm::READ, Flag::DEFAULT),
+                AclEntry::allow_other(Perm::READ, Flag::DEFAULT),
+            ]
+        );
+
+        // A required entry may not allow restricted permissions it does not
+        // need.
+        let policy = "[**]\nrequire group:eng:r\nrestrict w"
+            .parse::<AclPolicy>()
+            .unwrap();
+        let entries = vec![AclEntry::allow_group("eng", Perm::READ | Perm::WRITE, None)];
+        let violations = policy.check_entries(Path::new("x"), false, &entries);
+        assert_eq!(
+            violations,
+            [PolicyViolation {
+                path: PathBuf::from("x"),
+                kind: ViolationKind::Excess {
+                    entry: entries[0].clone(),
+                    perms: Perm::WRITE,
+                },
+            }]
+        );
+        assert_eq!(
+            correct_entries(entries, &violations),
+            [AclEntry::allow_group("eng", Perm::READ, None)]
+        );
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_policy_check_apply() -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        std::fs::write(&file, "")?;
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o666))?;
+
+        let text = format!(
+            "[{}/**]\nrequire group:500:rwx\nrequire d:group:500:rx\nrestrict w\n",
+            dir.path().display()
+        );
+        let policy = text.parse::<AclPolicy>().unwrap();
+
+        let violations = policy.check(dir.path())?;
+        assert_eq!(violations.len(), 5);
+        assert_eq!(policy.apply(dir.path())?, violations);
+        assert!(policy.check(dir.path())?.is_empty());
+
+        let entries = getfacl(&file, None)?;
+        assert_eq!(
+            crate::to_string(&entries)?,
+            "allow::user::read,write\nallow::group::read\nallow::group:500:read,write,execute\nallow::mask::read,write,execute\nallow::other::read\n"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1294 @@
+//! Implements a platform-neutral ACL data model.
+//!
+//! The native `AclEntryKind`, `Perm` and `Flag` types only contain the values
+//! supported by the current platform. The portable types defined here contain
+//! every kind, permission and flag supported by any platform, so an ACL
+//! collected on one platform can be stored, displayed and validated on
+//! another.
+//!
+//! A `PortableAclEntry` also keeps permission and flag names it doesn't
+//! recognize, so documents produced by a newer or different platform can be
+//! read and written back without losing data.
+
+use crate::aclentry::{parse_allow, AclEntry, AclEntryKind};
+use crate::failx::fail_custom;
+use crate::flag::Flag;
+use crate::format;
+use crate::perm::Perm;
+
+use bitflags::bitflags;
+#[cfg(feature = "serde")]
+use serde::{de, ser, Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::io;
+
+bitflags! {
+    /// Represents file access permissions on any platform.
+    ///
+    /// Unlike [`Perm`], all permissions are available on every platform. The
+    /// bit values are fixed and do not depend on the native ACL library.
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Default)]
+    pub struct PortablePerm : u32 {
+        /// READ_DATA permission for a file.
+        const READ = 1 << 2;
+
+        /// WRITE_DATA permission for a file.
+        const WRITE = 1 << 1;
+
+        /// EXECUTE permission for a file.
+        const EXECUTE = 1 << 0;
+
+        /// NFSv4 READ_DATA permission (`FreeBSD`).
+        const READ_DATA = 1 << 3;
+
+        /// NFSv4 WRITE_DATA permission (`FreeBSD`).
+        const WRITE_DATA = 1 << 4;
+
+        /// DELETE permission for a file (macOS, `FreeBSD`).
+        const DELETE = 1 << 5;
+
+        /// APPEND_DATA permission for a file (macOS, `FreeBSD`).
+        const APPEND = 1 << 6;
+
+        /// DELETE_CHILD permission for a directory (macOS, `FreeBSD`).
+        const DELETE_CHILD = 1 << 7;
+
+        /// READ_ATTRIBUTES permission (macOS, `FreeBSD`).
+        const READATTR = 1 << 8;
+
+        /// WRITE_ATTRIBUTES permission (macOS, `FreeBSD`).
+        const WRITEATTR = 1 << 9;
+
+        /// READ_EXTATTRIBUTES permission (macOS, `FreeBSD`).
+        const READEXTATTR = 1 << 10;
+
+        /// WRITE_EXTATTRIBUTES permission (macOS, `FreeBSD`).
+        const WRITEEXTATTR = 1 << 11;
+
+        /// READ_SECURITY permission (macOS, `FreeBSD`).
+        const READSECURITY = 1 << 12;
+
+        /// WRITE_SECURITY permission (macOS, `FreeBSD`).
+        const WRITESECURITY = 1 << 13;
+
+        /// CHANGE_OWNER permission (macOS, `FreeBSD`).
+        const CHOWN = 1 << 14;
+
+        /// SYNCHRONIZE permission (macOS, `FreeBSD`).
+        const SYNC = 1 << 15;
+    }
+}
+
+bitflags! {
+    /// Represents ACL entry inheritance flags on any platform.
+    ///
+    /// Unlike [`Flag`], all flags are available on every platform.
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Default)]
+    pub struct PortableFlag : u32 {
+        /// ACL entry was inherited (macOS, `FreeBSD`).
+        const INHERITED = 1 << 0;
+
+        /// Inherit to files (macOS, `FreeBSD`).
+        const FILE_INHERIT = 1 << 1;
+
+        /// Inherit to directories (macOS, `FreeBSD`).
+        const DIRECTORY_INHERIT = 1 << 2;
+
+        /// Clear the DIRECTORY_INHERIT flag in the ACL entry that is inherited
+        /// (macOS, `FreeBSD`).
+        const LIMIT_INHERIT = 1 << 3;
+
+        /// Don't consider this entry when processing the ACL. Just inherit it
+        /// (macOS, `FreeBSD`).
+        const ONLY_INHERIT = 1 << 4;
+
+        /// Specifies a default ACL entry (Linux, `FreeBSD`).
+        const DEFAULT = 1 << 5;
+    }
+}
+
+/// Kind of portable ACL entry.
+///
+/// Unlike [`AclEntryKind`], all kinds are available on every platform.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum PortableAclEntryKind {
+    /// Entry represents a user.
+    User,
+
+    /// Entry represents a group.
+    Group,
+
+    /// Entry represents a Posix.1e "mask" entry (Linux, `FreeBSD`).
+    Mask,
+
+    /// Entry represents a Posix.1e "other" entry (Linux, `FreeBSD`).
+    Other,
+
+    /// Entry represents a NFS "everyone" entry (`FreeBSD`).
+    Everyone,
+
+    /// Entry represents a possibly corrupt ACL entry, caused by an unknown tag.
+    Unknown,
+}
+
+/// Platform whose ACL semantics are used to validate a portable ACL entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum AclPlatform {
+    /// Linux Posix.1e ACL's.
+    Linux,
+
+    /// macOS extended ACL's.
+    MacOS,
+
+    /// `FreeBSD` Posix.1e and `NFSv4` ACL's.
+    FreeBSD,
+}
+
+/// Platform-neutral ACL entry with allow/deny semantics.
+///
+/// A `PortableAclEntry` can represent an ACL entry from any supported
+/// platform. Use [`PortableAclEntry::to_native`] to convert it to an
+/// [`AclEntry`] for the current platform, or
+/// [`PortableAclEntry::to_native_lossy`] to drop anything unsupported and
+/// report what was dropped.
+///
+/// Permission and flag names that are not recognized are not rejected.
+/// They are kept in `unknown_perms` and `unknown_flags`, and are written
+/// back out after the recognized names.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(from = "PortableAclEntryRepr", into = "PortableAclEntryRepr")
+)]
+pub struct PortableAclEntry {
+    /// Kind of entry (User, Group, Other, Mask, Everyone, or Unknown).
+    pub kind: PortableAclEntryKind,
+
+    /// Name of the principal being given access.
+    pub name: String,
+
+    /// Permission bits for the entry.
+    pub perms: PortablePerm,
+
+    /// Flags indicating whether an entry is inherited, etc.
+    pub flags: PortableFlag,
+
+    /// True if entry is allowed; false means deny.
+    pub allow: bool,
+
+    /// Permission names that are not recognized by this library.
+    pub unknown_perms: Vec<String>,
+
+    /// Flag names that are not recognized by this library.
+    pub unknown_flags: Vec<String>,
+}
+
+/// Serialized form of `PortableAclEntry`. Permissions and flags are lists of
+/// names, which may include names this library doesn't recognize.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PortableAclEntryRepr {
+    kind: PortableAclEntryKind,
+    name: String,
+    perms: Vec<String>,
+    #[serde(default)]
+    flags: Vec<String>,
+    #[serde(default = "default_allow")]
+    allow: bool,
+}
+
+// Default value of allow; used for serde.
+#[cfg(feature = "serde")]
+const fn default_allow() -> bool {
+    true
+}
+
+const KIND_NAMES: &[(PortableAclEntryKind, &str)] = &[
+    (PortableAclEntryKind::User, "user"),
+    (PortableAclEntryKind::Group, "group"),
+    (PortableAclEntryKind::Mask, "mask"),
+    (PortableAclEntryKind::Other, "other"),
+    (PortableAclEntryKind::Everyone, "everyone"),
+    (PortableAclEntryKind::Unknown, "unknown"),
+];
+
+const PERM_NAMES: &[(PortablePerm, &str)] = &[
+    (PortablePerm::READ, "read"),
+    (PortablePerm::WRITE, "write"),
+    (PortablePerm::EXECUTE, "execute"),
+    (PortablePerm::READ_DATA, "read_data"),
+    (PortablePerm::WRITE_DATA, "write_data"),
+    (PortablePerm::DELETE, "delete"),
+    (PortablePerm::APPEND, "append"),
+    (PortablePerm::DELETE_CHILD, "delete_child"),
+    (PortablePerm::READATTR, "readattr"),
+    (PortablePerm::WRITEATTR, "writeattr"),
+    (PortablePerm::READEXTATTR, "readextattr"),
+    (PortablePerm::WRITEEXTATTR, "writeextattr"),
+    (PortablePerm::READSECURITY, "readsecurity"),
+    (PortablePerm::WRITESECURITY, "writesecurity"),
+    (PortablePerm::CHOWN, "chown"),
+    (PortablePerm::SYNC, "sync"),
+];
+
+const FLAG_NAMES: &[(PortableFlag, &str)] = &[
+    (PortableFlag::INHERITED, "inherited"),
+    (PortableFlag::FILE_INHERIT, "file_inherit"),
+    (PortableFlag::DIRECTORY_INHERIT, "directory_inherit"),
+    (PortableFlag::LIMIT_INHERIT, "limit_inherit"),
+    (PortableFlag::ONLY_INHERIT, "only_inherit"),
+    (PortableFlag::DEFAULT, "default"),
+];
+
+// Mapping between portable and native permissions on this platform.
+const NATIVE_PERMS: &[(PortablePerm, Perm)] = &[
+    (PortablePerm::READ, Perm::READ),
+    (PortablePerm::WRITE, Perm::WRITE),
+    (PortablePerm::EXECUTE, Perm::EXECUTE),
+    #[cfg(target_os = "freebsd")]
+    (PortablePerm::READ_DATA, Perm::READ_DATA),
+    #[cfg(target_os = "freebsd")]
+    (PortablePerm::WRITE_DATA, Perm::WRITE_DATA),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::DELETE, Perm::DELETE),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::APPEND, Perm::APPEND),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::DELETE_CHILD, Perm::DELETE_CHILD),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::READATTR, Perm::READATTR),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::WRITEATTR, Perm::WRITEATTR),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::READEXTATTR, Perm::READEXTATTR),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::WRITEEXTATTR, Perm::WRITEEXTATTR),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::READSECURITY, Perm::READSECURITY),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::WRITESECURITY, Perm::WRITESECURITY),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::CHOWN, Perm::CHOWN),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortablePerm::SYNC, Perm::SYNC),
+];
+
+// Substitutes for permissions that are not supported natively. READ_DATA and
+// WRITE_DATA have the same meaning as READ and WRITE on a file.
+#[cfg(not(target_os = "freebsd"))]
+const FALLBACK_PERMS: &[(PortablePerm, PortablePerm)] = &[
+    (PortablePerm::READ_DATA, PortablePerm::READ),
+    (PortablePerm::WRITE_DATA, PortablePerm::WRITE),
+];
+
+#[cfg(target_os = "freebsd")]
+const FALLBACK_PERMS: &[(PortablePerm, PortablePerm)] = &[];
+
+// Mapping between portable and native flags on this platform.
+const NATIVE_FLAGS: &[(PortableFlag, Flag)] = &[
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortableFlag::INHERITED, Flag::INHERITED),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortableFlag::FILE_INHERIT, Flag::FILE_INHERIT),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortableFlag::DIRECTORY_INHERIT, Flag::DIRECTORY_INHERIT),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortableFlag::LIMIT_INHERIT, Flag::LIMIT_INHERIT),
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    (PortableFlag::ONLY_INHERIT, Flag::ONLY_INHERIT),
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    (PortableFlag::DEFAULT, Flag::DEFAULT),
+];
+
+/// Return the name of a single bit using the given (bit, str) table.
+fn bit_name<T: PartialEq + Copy>(bit: T, table: &[(T, &'static str)]) -> Option<&'static str> {
+    table.iter().find(|item| item.0 == bit).map(|item| item.1)
+}
+
+/// Look up a name using the given (value, str) table.
+fn read_name<T: Copy>(s: &str, table: &[(T, &str)]) -> Result<T, format::Error> {
+    match table.iter().find(|item| item.1 == s) {
+        Some((value, _)) => Ok(*value),
+        None => {
+            let variants = table
+                .iter()
+                .map(|item| format!("`{}`", item.1))
+                .collect::<Vec<String>>()
+                .join(", ");
+            Err(format::Error::Message(format!(
+                "unknown variant `{s}`, expected one of {variants}"
+            )))
+        }
+    }
+}
+
+/// Write the names of the bits in `value`, separated by commas.
+fn write_names<T>(f: &mut fmt::Formatter, value: T, table: &[(T, &str)]) -> fmt::Result
+where
+    T: bitflags::Flags + Copy,
+{
+    let mut first = true;
+    for (bit, name) in table {
+        if value.contains(*bit) {
+            if !first {
+                f.write_str(",")?;
+            }
+            f.write_str(name)?;
+            first = false;
+        }
+    }
+    Ok(())
+}
+
+/// Split a list of names into known bits and unrecognized names.
+#[cfg(feature = "serde")]
+fn split_names<T, I>(names: I, table: &[(T, &str)]) -> (T, Vec<String>)
+where
+    T: bitflags::Flags + Copy,
+    I: IntoIterator<Item = String>,
+{
+    let mut known = T::empty();
+    let mut unknown = Vec::new();
+    for name in names {
+        match table.iter().find(|item| item.1 == name) {
+            Some((bit, _)) => known.insert(*bit),
+            None if !unknown.contains(&name) => unknown.push(name),
+            None => (),
+        }
+    }
+    (known, unknown)
+}
+
+/// Return true if `word` could be a permission or flag name.
+fn is_identifier(word: &str) -> bool {
+    word.chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+        && word.starts_with(|ch: char| ch.is_ascii_alphabetic())
+}
+
+impl PortablePerm {
+    /// Return the permission names in this set.
+    pub(crate) fn names(self) -> impl Iterator<Item = &'static str> {
+        PERM_NAMES
+            .iter()
+            .filter(move |item| self.contains(item.0))
+            .map(|item| item.1)
+    }
+
+    /// Return the set of permissions supported on the given platform.
+    #[must_use]
+    pub const fn supported_on(platform: AclPlatform) -> PortablePerm {
+        match platform {
+            AclPlatform::Linux => PortablePerm::from_bits_retain(
+                PortablePerm::READ.bits()
+                    | PortablePerm::WRITE.bits()
+                    | PortablePerm::EXECUTE.bits(),
+            ),
+            AclPlatform::MacOS => PortablePerm::from_bits_retain(
+                PortablePerm::all().bits()
+                    & !(PortablePerm::READ_DATA.bits() | PortablePerm::WRITE_DATA.bits()),
+            ),
+            AclPlatform::FreeBSD => PortablePerm::all(),
+        }
+    }
+}
+
+impl PortableFlag {
+    /// Return the flag names in this set.
+    pub(crate) fn names(self) -> impl Iterator<Item = &'static str> {
+        FLAG_NAMES
+            .iter()
+            .filter(move |item| self.contains(item.0))
+            .map(|item| item.1)
+    }
+
+    /// Return the set of flags supported on the given platform.
+    #[must_use]
+    pub const fn supported_on(platform: AclPlatform) -> PortableFlag {
+        match platform {
+            AclPlatform::Linux => PortableFlag::DEFAULT,
+            AclPlatform::MacOS => PortableFlag::from_bits_retain(
+                PortableFlag::all().bits() & !PortableFlag::DEFAULT.bits(),
+            ),
+            AclPlatform::FreeBSD => PortableFlag::all(),
+        }
+    }
+}
+
+impl PortableAclEntryKind {
+    /// Return true if the kind is supported on the given platform.
+    #[must_use]
+    pub const fn is_supported_on(self, platform: AclPlatform) -> bool {
+        match self {
+            PortableAclEntryKind::User
+            | PortableAclEntryKind::Group
+            | PortableAclEntryKind::Unknown => true,
+            PortableAclEntryKind::Mask | PortableAclEntryKind::Other => {
+                !matches!(platform, AclPlatform::MacOS)
+            }
+            PortableAclEntryKind::Everyone => matches!(platform, AclPlatform::FreeBSD),
+        }
+    }
+}
+
+impl AclPlatform {
+    /// Return the platform this library was compiled for.
+    #[must_use]
+    pub const fn current() -> AclPlatform {
+        #[cfg(target_os = "linux")]
+        return AclPlatform::Linux;
+
+        #[cfg(target_os = "macos")]
+        return AclPlatform::MacOS;
+
+        #[cfg(target_os = "freebsd")]
+        return AclPlatform::FreeBSD;
+    }
+}
+
+impl fmt::Display for AclPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AclPlatform::Linux => "linux",
+            AclPlatform::MacOS => "macos",
+            AclPlatform::FreeBSD => "freebsd",
+        };
+        f.write_str(name)
+    }
+}
+
+impl From<Perm> for PortablePerm {
+    /// Convert native permissions. Unnamed native bits are ignored.
+    fn from(perms: Perm) -> Self {
+        let mut result = PortablePerm::empty();
+        for (portable, native) in NATIVE_PERMS {
+            if perms.contains(*native) {
+                result |= *portable;
+            }
+        }
+        result
+    }
+}
+
+impl TryFrom<PortablePerm> for Perm {
+    type Error = io::Error;
+
+    /// Convert portable permissions to native permissions.
+    ///
+    /// Fails if any permission is not supported on this platform.
+    fn try_from(perms: PortablePerm) -> io::Result<Self> {
+        let mut result = Perm::empty();
+        let mut remaining = perms;
+        for (portable, native) in NATIVE_PERMS {
+            if perms.contains(*portable) {
+                result |= *native;
+                remaining.remove(*portable);
+            }
+        }
+
+        if let Some(name) = remaining.names().next() {
+            return fail_custom(&format!("unsupported permission `{name}`"));
+        }
+
+        Ok(result)
+    }
+}
+
+impl From<Flag> for PortableFlag {
+    /// Convert native flags. Unnamed native bits are ignored.
+    fn from(flags: Flag) -> Self {
+        let mut result = PortableFlag::empty();
+        for (portable, native) in NATIVE_FLAGS {
+            if flags.contains(*native) {
+                result |= *portable;
+            }
+        }
+        result
+    }
+}
+
+impl TryFrom<PortableFlag> for Flag {
+    type Error = io::Error;
+
+    /// Convert portable flags to native flags.
+    ///
+    /// Fails if any flag is not supported on this platform.
+    fn try_from(flags: PortableFlag) -> io::Result<Self> {
+        let mut result = Flag::empty();
+        let mut remaining = flags;
+        for (portable, native) in NATIVE_FLAGS {
+            if flags.contains(*portable) {
+                result |= *native;
+                remaining.remove(*portable);
+            }
+        }
+
+        if let Some(name) = remaining.names().next() {
+            return fail_custom(&format!("unsupported flag `{name}`"));
+        }
+
+        Ok(result)
+    }
+}
+
+impl From<AclEntryKind> for PortableAclEntryKind {
+    fn from(kind: AclEntryKind) -> Self {
+        match kind {
+            AclEntryKind::User => PortableAclEntryKind::User,
+            AclEntryKind::Group => PortableAclEntryKind::Group,
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            AclEntryKind::Mask => PortableAclEntryKind::Mask,
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            AclEntryKind::Other => PortableAclEntryKind::Other,
+            #[cfg(target_os = "freebsd")]
+            AclEntryKind::Everyone => PortableAclEntryKind::Everyone,
+            AclEntryKind::Unknown => PortableAclEntryKind::Unknown,
+        }
+    }
+}
+
+impl TryFrom<PortableAclEntryKind> for AclEntryKind {
+    type Error = io::Error;
+
+    /// Convert a portable kind to a native kind.
+    ///
+    /// Fails if the kind is not supported on this platform.
+    fn try_from(kind: PortableAclEntryKind) -> io::Result<Self> {
+        let result = match kind {
+            PortableAclEntryKind::User => AclEntryKind::User,
+            PortableAclEntryKind::Group => AclEntryKind::Group,
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            PortableAclEntryKind::Mask => AclEntryKind::Mask,
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            PortableAclEntryKind::Other => AclEntryKind::Other,
+            #[cfg(target_os = "freebsd")]
+            PortableAclEntryKind::Everyone => AclEntryKind::Everyone,
+            PortableAclEntryKind::Unknown => AclEntryKind::Unknown,
+            #[allow(unreachable_patterns)]
+            kind => return fail_custom(&format!("unsupported kind `{kind}`")),
+        };
+
+        Ok(result)
+    }
+}
+
+impl PortableAclEntry {
+    /// Construct a new portable access control entry.
+    #[must_use]
+    pub fn new(
+        kind: PortableAclEntryKind,
+        name: &str,
+        perms: PortablePerm,
+        flags: PortableFlag,
+        allow: bool,
+    ) -> PortableAclEntry {
+        PortableAclEntry {
+            kind,
+            name: String::from(name),
+            perms,
+            flags,
+            allow,
+            unknown_perms: Vec::new(),
+            unknown_flags: Vec::new(),
+        }
+    }
+
+    /// Convert this entry to a native [`AclEntry`] for the current platform.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the entry uses a kind, permission or flag
+    /// that is not supported on this platform, or if it is a deny entry on
+    /// Linux.
+    pub fn to_native(&self) -> io::Result<AclEntry> {
+        if let Some(name) = self.unknown_perms.first() {
+            return fail_custom(&format!("unknown permission `{name}`"));
+        }
+        if let Some(name) = self.unknown_flags.first() {
+            return fail_custom(&format!("unknown flag `{name}`"));
+        }
+
+        let kind = AclEntryKind::try_from(self.kind)?;
+        let perms = Perm::try_from(self.perms)?;
+        let flags = Flag::try_from(self.flags)?;
+
+        if !self.allow && cfg!(target_os = "linux") {
+            return fail_custom("allow=false is not supported");
+        }
+
+        Ok(AclEntry {
+            kind,
+            name: self.name.clone(),
+            perms,
+            flags,
+            allow: self.allow,
+        })
+    }
+
+    /// Convert this entry to a native [`AclEntry`], dropping anything that is
+    /// not supported on the current platform.
+    ///
+    /// Returns the native entry, or `None` if the entry's kind is not
+    /// supported, along with a message for each permission or flag that was
+    /// mapped to a substitute or dropped. A deny entry is dropped on Linux,
+    /// since allowing its permissions instead would grant access.
+    #[must_use]
+    pub fn to_native_lossy(&self) -> (Option<AclEntry>, Vec<String>) {
+        let mut report = Vec::new();
+
+        let Ok(kind) = AclEntryKind::try_from(self.kind) else {
+            report.push(format!("unsupported kind `{}`; entry dropped", self.kind));
+            return (None, report);
+        };
+
+        if !self.allow && cfg!(target_os = "linux") {
+            report.push(String::from("unsupported allow=false; entry dropped"));
+            return (None, report);
+        }
+
+        let mut perms = Perm::empty();
+        for name in self.perms.names() {
+            let bit = read_name(name, PERM_NAMES).expect("known name");
+            if let Ok(native) = Perm::try_from(bit) {
+                perms |= native;
+            } else if let Some((_, substitute)) = FALLBACK_PERMS
+                .iter()
+                .find(|item| item.0 == bit && Perm::try_from(item.1).is_ok())
+            {
+                perms |= Perm::try_from(*substitute).expect("supported substitute");
+                report.push(format!("mapped permission `{name}` to `{substitute}`"));
+            } else {
+                report.push(format!("dropped unsupported permission `{name}`"));
+            }
+        }
+
+        let mut flags = Flag::empty();
+        for name in self.flags.names() {
+            let bit = read_name(name, FLAG_NAMES).expect("known name");
+            match Flag::try_from(bit) {
+                Ok(native) => flags |= native,
+                Err(_) => report.push(format!("dropped unsupported flag `{name}`")),
+            }
+        }
+
+        for name in &self.unknown_perms {
+            report.push(format!("dropped unknown permission `{name}`"));
+        }
+        for name in &self.unknown_flags {
+            report.push(format!("dropped unknown flag `{name}`"));
+        }
+
+        let entry = AclEntry {
+            kind,
+            name: self.name.clone(),
+            perms,
+            flags,
+            allow: self.allow,
+        };
+
+        (Some(entry), report)
+    }
+
+    /// Check that this entry is valid on the given platform.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] describing the first unsupported kind,
+    /// permission or flag.
+    pub fn validate(&self, platform: AclPlatform) -> io::Result<()> {
+        if let Some(name) = self.unknown_perms.first() {
+            return fail_custom(&format!("unknown permission `{name}`"));
+        }
+
+        if let Some(name) = self.unknown_flags.first() {
+            return fail_custom(&format!("unknown flag `{name}`"));
+        }
+
+        if !self.kind.is_supported_on(platform) {
+            return fail_custom(&format!("unsupported kind `{}` on {platform}", self.kind));
+        }
+
+        let perms = self.perms - PortablePerm::supported_on(platform);
+        if let Some(name) = perms.names().next() {
+            return fail_custom(&format!("unsupported permission `{name}` on {platform}"));
+        }
+
+        let flags = self.flags - PortableFlag::supported_on(platform);
+        if let Some(name) = flags.names().next() {
+            return fail_custom(&format!("unsupported flag `{name}` on {platform}"));
+        }
+
+        if !self.allow && platform == AclPlatform::Linux {
+            return fail_custom(&format!("allow=false is not supported on {platform}"));
+        }
+
+        Ok(())
+    }
+
+    /// Convert a slice of native entries to portable entries.
+    #[must_use]
+    pub fn from_native_entries(entries: &[AclEntry]) -> Vec<PortableAclEntry> {
+        entries.iter().map(PortableAclEntry::from).collect()
+    }
+
+    /// Convert a slice of portable entries to native entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if any entry is not supported on this
+    /// platform.
+    pub fn to_native_entries(entries: &[PortableAclEntry]) -> io::Result<Vec<AclEntry>> {
+        let mut result = Vec::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            match entry.to_native() {
+                Ok(native) => result.push(native),
+                Err(err) => return fail_custom(&format!("entry {i}: {err}")),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Convert a slice of portable entries to native entries, dropping
+    /// anything that is not supported on the current platform.
+    ///
+    /// Returns the native entries and a report of everything that was mapped
+    /// or dropped. Each message in the report is prefixed by the index of
+    /// the portable entry.
+    #[must_use]
+    pub fn to_native_entries_lossy(entries: &[PortableAclEntry]) -> (Vec<AclEntry>, Vec<String>) {
+        let mut result = Vec::with_capacity(entries.len());
+        let mut report = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let (native, messages) = entry.to_native_lossy();
+            result.extend(native);
+            report.extend(messages.into_iter().map(|msg| format!("entry {i}: {msg}")));
+        }
+        (result, report)
+    }
+}
+
+impl From<&AclEntry> for PortableAclEntry {
+    fn from(entry: &AclEntry) -> Self {
+        PortableAclEntry {
+            kind: entry.kind.into(),
+            name: entry.name.clone(),
+            perms: entry.perms.into(),
+            flags: entry.flags.into(),
+            allow: entry.allow,
+            unknown_perms: Vec::new(),
+            unknown_flags: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<PortableAclEntryRepr> for PortableAclEntry {
+    fn from(repr: PortableAclEntryRepr) -> Self {
+        let (perms, unknown_perms) = split_names(repr.perms, PERM_NAMES);
+        let (flags, unknown_flags) = split_names(repr.flags, FLAG_NAMES);
+
+        PortableAclEntry {
+            kind: repr.kind,
+            name: repr.name,
+            perms,
+            flags,
+            allow: repr.allow,
+            unknown_perms,
+            unknown_flags,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<PortableAclEntry> for PortableAclEntryRepr {
+    fn from(entry: PortableAclEntry) -> Self {
+        let perms = entry.perms.names().map(String::from);
+        let flags = entry.flags.names().map(String::from);
+
+        PortableAclEntryRepr {
+            kind: entry.kind,
+            name: entry.name,
+            perms: perms.chain(entry.unknown_perms).collect(),
+            flags: flags.chain(entry.unknown_flags).collect(),
+            allow: entry.allow,
+        }
+    }
+}
+
+impl TryFrom<&PortableAclEntry> for AclEntry {
+    type Error = io::Error;
+
+    fn try_from(entry: &PortableAclEntry) -> io::Result<Self> {
+        entry.to_native()
+    }
+}
+
+impl Ord for PortableAclEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Use the same canonical order as `AclEntry`: entries with flags last,
+        // denied entries first, then order by kind and name.
+        match (self.flags.is_empty(), other.flags.is_empty()) {
+            (true, false) => return Ordering::Less,
+            (false, true) => return Ordering::Greater,
+            _ => (),
+        }
+
+        self.allow
+            .cmp(&other.allow)
+            .then_with(|| self.kind.cmp(&other.kind))
+            .then_with(|| self.name.cmp(&other.name))
+    }
+}
+
+impl PartialOrd for PortableAclEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for PortableAclEntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(bit_name(*self, KIND_NAMES).unwrap_or("!!"))
+    }
+}
+
+impl fmt::Display for PortablePerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_names(f, *self, PERM_NAMES)
+    }
+}
+
+impl fmt::Display for PortableFlag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_names(f, *self, FLAG_NAMES)
+    }
+}
+
+impl fmt::Display for PortableAclEntry {
+    /// Format a `PortableAclEntry` 5-tuple:
+    ///   `<allow>:<flags>:<kind>:<name>:<perms>`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let allow = if self.allow { "allow" } else { "deny" };
+        let flags = self
+            .flags
+            .names()
+            .map(String::from)
+            .chain(self.unknown_flags.iter().cloned())
+            .collect::<Vec<String>>()
+            .join(",");
+        let perms = self
+            .perms
+            .names()
+            .map(String::from)
+            .chain(self.unknown_perms.iter().cloned())
+            .collect::<Vec<String>>()
+            .join(",");
+
+        write!(f, "{allow}:{flags}:{}:{}:{perms}", self.kind, self.name)
+    }
+}
+
+impl std::str::FromStr for PortableAclEntryKind {
+    type Err = format::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "u" => Ok(PortableAclEntryKind::User),
+            "g" => Ok(PortableAclEntryKind::Group),
+            "o" => Ok(PortableAclEntryKind::Other),
+            "m" => Ok(PortableAclEntryKind::Mask),
+            _ => read_name(s, KIND_NAMES),
+        }
+    }
+}
+
+/// Parse an abbreviated permission, "rwx", "wx", "r-x" etc.
+fn parse_perm_abbreviation(s: &str) -> Option<PortablePerm> {
+    let mut perms = PortablePerm::empty();
+    for ch in s.chars() {
+        match ch {
+            'r' if !perms.contains(PortablePerm::READ) => perms |= PortablePerm::READ,
+            'w' if !perms.contains(PortablePerm::WRITE) => perms |= PortablePerm::WRITE,
+            'x' if !perms.contains(PortablePerm::EXECUTE) => perms |= PortablePerm::EXECUTE,
+            '-' => (),
+            _ => return None,
+        }
+    }
+    Some(perms)
+}
+
+impl std::str::FromStr for PortablePerm {
+    type Err = format::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = PortablePerm::empty();
+
+        for item in s.split(',') {
+            let word = item.trim();
+            if !word.is_empty() {
+                if let Some(perms) = parse_perm_abbreviation(word) {
+                    result |= perms;
+                } else {
+                    result |= read_name(word, PERM_NAMES)?;
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl std::str::FromStr for PortableFlag {
+    type Err = format::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = PortableFlag::empty();
+
+        for item in s.split(',') {
+            let word = item.trim();
+            if word == "d" {
+                result |= PortableFlag::DEFAULT;
+            } else if !word.is_empty() {
+                result |= read_name(word, FLAG_NAMES)?;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl std::str::FromStr for PortableAclEntry {
+    type Err = format::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.splitn(5, ':').map(str::trim).collect::<Vec<&str>>();
+
+        let (allow, flags, rest) = match fields.len() {
+            5 => (parse_allow(fields[0])?, fields[1], &fields[2..]),
+            // A 4-field line that starts with `allow` or `deny` is missing
+            // its flags field; it is not an entry with an unknown flag.
+            4 if parse_allow(fields[0]).is_ok() => {
+                return Err(format::Error::Message(format!("Unknown ACL format: `{s}`")))
+            }
+            4 => (true, fields[0], &fields[1..]),
+            3 => (true, "", &fields[..]),
+            _ => return Err(format::Error::Message(format!("Unknown ACL format: `{s}`"))),
+        };
+
+        let kind = rest[0].parse::<PortableAclEntryKind>()?;
+        let mut entry = PortableAclEntry::new(
+            kind,
+            rest[1],
+            PortablePerm::empty(),
+            PortableFlag::empty(),
+            allow,
+        );
+
+        // Unrecognized names are kept, as long as they look like names.
+        for item in flags.split(',') {
+            let word = item.trim();
+            match word.parse::<PortableFlag>() {
+                Ok(flag) => entry.flags |= flag,
+                Err(_) if is_identifier(word) => entry.unknown_flags.push(word.to_string()),
+                Err(err) => return Err(err),
+            }
+        }
+
+        for item in rest[2].split(',') {
+            let word = item.trim();
+            match word.parse::<PortablePerm>() {
+                Ok(perm) => entry.perms |= perm,
+                Err(_) if is_identifier(word) => entry.unknown_perms.push(word.to_string()),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(entry)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ser::Serialize for PortablePerm {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_seq(self.names())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> de::Deserialize<'de> for PortablePerm {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let mut perms = PortablePerm::empty();
+        for name in Vec::<String>::deserialize(deserializer)? {
+            perms |= read_name(&name, PERM_NAMES).map_err(de::Error::custom)?;
+        }
+        Ok(perms)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ser::Serialize for PortableFlag {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_seq(self.names())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> de::Deserialize<'de> for PortableFlag {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let mut flags = PortableFlag::empty();
+        for name in Vec::<String>::deserialize(deserializer)? {
+            flags |= read_name(&name, FLAG_NAMES).map_err(de::Error::custom)?;
+        }
+        Ok(flags)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod portable_tests {
+    use super::*;
+
+    #[test]
+    fn test_portable_display() {
+        let entry = PortableAclEntry::new(
+            PortableAclEntryKind::User,
+            "x",
+            PortablePerm::READ | PortablePerm::DELETE_CHILD,
+            PortableFlag::FILE_INHERIT | PortableFlag::INHERITED,
+            false,
+        );
+        assert_eq!(
+            entry.to_string(),
+            "deny:inherited,file_inherit:user:x:read,delete_child"
+        );
+
+        assert_eq!(PortablePerm::all().to_string(), "read,write,execute,read_data,write_data,delete,append,delete_child,readattr,writeattr,readextattr,writeextattr,readsecurity,writesecurity,chown,sync");
+        assert_eq!(
+            PortableFlag::all().to_string(),
+            "inherited,file_inherit,directory_inherit,limit_inherit,only_inherit,default"
+        );
+    }
+
+    #[test]
+    fn test_portable_fromstr() {
+        let values = [
+            ("u:admin:rwx", "allow::user:admin:read,write,execute"),
+            ("d:m::r-x", "allow:default:mask::read,execute"),
+            ("everyone::read_data", "allow::everyone::read_data"),
+            (
+                "deny:file_inherit:group:staff:delete,chown",
+                "deny:file_inherit:group:staff:delete,chown",
+            ),
+        ];
+
+        for (input, expected) in &values {
+            let entry = input.parse::<PortableAclEntry>().unwrap();
+            assert_eq!(*expected, entry.to_string());
+        }
+
+        let entry = "allow:audit:user:x:read,bogus"
+            .parse::<PortableAclEntry>()
+            .unwrap();
+        assert_eq!(entry.perms, PortablePerm::READ);
+        assert_eq!(entry.unknown_perms, ["bogus"]);
+        assert_eq!(entry.unknown_flags, ["audit"]);
+        assert_eq!(entry.to_string(), "allow:audit:user:x:read,bogus");
+
+        let err = "user:x:read,b@d".parse::<PortableAclEntry>().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("unknown variant `b@d`, expected one of `read`, `write`"));
+
+        let err = "allow:foo".parse::<PortableAclEntry>().unwrap_err();
+        assert_eq!(err.to_string(), "Unknown ACL format: `allow:foo`");
+
+        let err = "deny:user:x:read".parse::<PortableAclEntry>().unwrap_err();
+        assert_eq!(err.to_string(), "Unknown ACL format: `deny:user:x:read`");
+    }
+
+    #[test]
+    fn test_portable_native_roundtrip() {
+        let native = AclEntry::allow_group("staff", Perm::READ | Perm::EXECUTE, None);
+        let portable = PortableAclEntry::from(&native);
+        assert_eq!(portable.to_string(), "allow::group:staff:read,execute");
+        assert_eq!(portable.to_native().unwrap(), native);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_portable_to_native_unsupported() {
+        let entry = "deny:file_inherit:user:x:read"
+            .parse::<PortableAclEntry>()
+            .unwrap();
+        assert_eq!(
+            entry.to_native().unwrap_err().to_string(),
+            "unsupported flag `file_inherit`"
+        );
+
+        let entry = "user:x:read,delete".parse::<PortableAclEntry>().unwrap();
+        assert_eq!(
+            entry.to_native().unwrap_err().to_string(),
+            "unsupported permission `delete`"
+        );
+
+        let entry = "everyone::read".parse::<PortableAclEntry>().unwrap();
+        assert_eq!(
+            entry.to_native().unwrap_err().to_string(),
+            "unsupported kind `everyone`"
+        );
+
+        let entries = [
+            "user::rw".parse::<PortableAclEntry>().unwrap(),
+            "group:x:sync".parse::<PortableAclEntry>().unwrap(),
+        ];
+        assert_eq!(
+            PortableAclEntry::to_native_entries(&entries)
+                .unwrap_err()
+                .to_string(),
+            "entry 1: unsupported permission `sync`"
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_portable_to_native_lossy() {
+        let entries = [
+            "file_inherit,audit:user:x:read_data,delete,write,append_only"
+                .parse::<PortableAclEntry>()
+                .unwrap(),
+            "everyone::read".parse::<PortableAclEntry>().unwrap(),
+            "group::rx".parse::<PortableAclEntry>().unwrap(),
+            "deny::user:x:write".parse::<PortableAclEntry>().unwrap(),
+        ];
+
+        let err = entries[3].to_native().unwrap_err();
+        assert_eq!(err.to_string(), "allow=false is not supported");
+
+        let err = entries[0].to_native().unwrap_err();
+        assert_eq!(err.to_string(), "unknown permission `append_only`");
+
+        let (native, report) = PortableAclEntry::to_native_entries_lossy(&entries);
+        assert_eq!(
+            native,
+            [
+                AclEntry::allow_user("x", Perm::READ | Perm::WRITE, None),
+                AclEntry::allow_group("", Perm::READ | Perm::EXECUTE, None),
+            ]
+        );
+        assert_eq!(
+            report,
+            [
+                "entry 0: mapped permission `read_data` to `read`",
+                "entry 0: dropped unsupported permission `delete`",
+                "entry 0: dropped unsupported flag `file_inherit`",
+                "entry 0: dropped unknown permission `append_only`",
+                "entry 0: dropped unknown flag `audit`",
+                "entry 1: unsupported kind `everyone`; entry dropped",
+                "entry 3: unsupported allow=false; entry dropped",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_portable_validate() {
+        let entry = "deny:file_inherit:user:x:read,delete"
+            .parse::<PortableAclEntry>()
+            .unwrap();
+        assert!(entry.validate(AclPlatform::MacOS).is_ok());
+        assert!(entry.validate(AclPlatform::FreeBSD).is_ok());
+        assert_eq!(
+            entry.validate(AclPlatform::Linux).unwrap_err().to_string(),
+            "unsupported permission `delete` on linux"
+        );
+
+        let entry = "d:other::rx".parse::<PortableAclEntry>().unwrap();
+        assert!(entry.validate(AclPlatform::Linux).is_ok());
+        assert_eq!(
+            entry.validate(AclPlatform::MacOS).unwrap_err().to_string(),
+            "unsupported kind `other` on macos"
+        );
+
+        let entry = "deny::user:x:read".parse::<PortableAclEntry>().unwrap();
+        assert_eq!(
+            entry.validate(AclPlatform::Linux).unwrap_err().to_string(),
+            "allow=false is not supported on linux"
+        );
+    }
+
+    #[test]
+    fn test_portable_ordering() {
+        let mut entries = [
+            "d:user:a:r".parse::<PortableAclEntry>().unwrap(),
+            "other::r".parse::<PortableAclEntry>().unwrap(),
+            "deny::group:b:w".parse::<PortableAclEntry>().unwrap(),
+            "user:c:x".parse::<PortableAclEntry>().unwrap(),
+        ];
+        entries.sort();
+
+        let sorted = entries
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            sorted,
+            [
+                "deny::group:b:write",
+                "allow::user:c:execute",
+                "allow::other::read",
+                "allow:default:user:a:read",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_portable_serde() {
+        let json = r#"[{"kind":"user","name":"x","perms":["read","delete_child"],"flags":["file_inherit"],"allow":false}]"#;
+        let entries: Vec<PortableAclEntry> = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            entries[0].to_string(),
+            "deny:file_inherit:user:x:read,delete_child"
+        );
+        assert_eq!(serde_json::to_string(&entries).unwrap(), json);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_portable_serde_unknown() {
+        let json = r#"{"kind":"group","name":"y","perms":["read","frobnicate"],"flags":["audit","default"],"allow":true}"#;
+        let entry: PortableAclEntry = serde_json::from_str(json).unwrap();
+        assert_eq!(entry.perms, PortablePerm::READ);
+        assert_eq!(entry.flags, PortableFlag::DEFAULT);
+        assert_eq!(entry.unknown_perms, ["frobnicate"]);
+        assert_eq!(entry.unknown_flags, ["audit"]);
+
+        let expected = r#"{"kind":"group","name":"y","perms":["read","frobnicate"],"flags":["default","audit"],"allow":true}"#;
+        assert_eq!(serde_json::to_string(&entry).unwrap(), expected);
+
+        // Unknown fields and kinds are still rejected.
+        let json = r#"{"kind":"group","name":"y","perms":[],"extra":1}"#;
+        assert!(serde_json::from_str::<PortableAclEntry>(json).is_err());
+        let json = r#"{"kind":"bogus","name":"y","perms":[]}"#;
+        assert!(serde_json::from_str::<PortableAclEntry>(json).is_err());
+    }
+}
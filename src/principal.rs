@@ -0,0 +1,398 @@
+//! Implements the `Principal` type.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::format;
+use crate::qualifier::Qualifier;
+use crate::raw::RawAclEntry;
+use crate::unix;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// User or group that an ACL entry applies to.
+///
+/// [`AclEntry::name`] is a single string that may be a user or group name, a
+/// decimal uid/gid, a GUID or `""`. A `Principal` keeps the numeric id and
+/// the resolved name apart, so a uid without a name can be told from a
+/// user whose name happens to be a number.
+///
+/// The text form is `uid=1234(alice)` or `uid=1234` for users, `gid=20(staff)`
+/// or `gid=20` for groups, `guid=<uuid>` for a macOS GUID without a uid or
+/// gid, `unknown=<tag>` for an entry with an unrecognized tag, such as
+/// `unknown=@tag 42`, and `owner`, `group_owner`, `mask`, `other` or
+/// `everyone` for the entries without a qualifier. Parsing the text form
+/// returns the same principal.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Principal {
+    /// The file owner, for the unnamed user entry.
+    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+    Owner,
+
+    /// The owning group of the file, for the unnamed group entry.
+    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+    GroupOwner,
+
+    /// The Posix.1e "mask" entry.
+    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+    Mask,
+
+    /// The Posix.1e "other" entry.
+    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+    Other,
+
+    /// The NFS "everyone" entry.
+    #[cfg(any(docsrs, target_os = "freebsd"))]
+    #[cfg_attr(docsrs, doc(cfg(target_os = "freebsd")))]
+    Everyone,
+
+    /// User with a uid, and the user's name if the uid has one.
+    User {
+        /// Numeric user ID.
+        id: u32,
+        /// Name of the user, or `None` if the uid has no name.
+        name: Option<String>,
+    },
+
+    /// Group with a gid, and the group's name if the gid has one.
+    Group {
+        /// Numeric group ID.
+        id: u32,
+        /// Name of the group, or `None` if the gid has no name.
+        name: Option<String>,
+    },
+
+    /// macOS GUID that does not map to a uid or gid.
+    Guid(String),
+
+    /// Unrecognized qualifier.
+    Unknown(String),
+}
+
+impl Principal {
+    /// Return the numeric uid or gid.
+    #[must_use]
+    pub fn id(&self) -> Option<u32> {
+        match self {
+            Principal::User { id, .. } | Principal::Group { id, .. } => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Return the resolved user or group name.
+    ///
+    /// Returns `None` if the uid or gid has no name, or if the principal is
+    /// not a user or group.
+    #[must_use]
+    pub fn resolved_name(&self) -> Option<&str> {
+        match self {
+            Principal::User { name, .. } | Principal::Group { name, .. } => name.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Return the name to use in an [`AclEntry`].
+    ///
+    /// This is the resolved name if there is one, otherwise the decimal id
+    /// or the GUID. Entries without a qualifier, such as the owner, have the
+    /// name `""`.
+    #[must_use]
+    pub fn to_entry_name(&self) -> String {
+        match self {
+            Principal::User { id, name } | Principal::Group { id, name } => {
+                name.clone().unwrap_or_else(|| id.to_string())
+            }
+            Principal::Guid(s) | Principal::Unknown(s) => s.clone(),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            _ => String::new(),
+        }
+    }
+
+    /// Return the kind of entry for a user or group, or `None` for other
+    /// principals.
+    pub(crate) const fn entry_kind(&self) -> Option<AclEntryKind> {
+        match self {
+            Principal::User { .. } => Some(AclEntryKind::User),
+            Principal::Group { .. } => Some(AclEntryKind::Group),
+            _ => None,
+        }
+    }
+
+    /// Return the principal for a qualifier, looking up its name.
+    pub(crate) fn from_qualifier(qualifier: &Qualifier) -> io::Result<Principal> {
+        let principal = match qualifier {
+            Qualifier::User(uid) => Principal::User {
+                id: *uid,
+                name: unix::find_user_name(*uid)?,
+            },
+            Qualifier::Group(gid) => Principal::Group {
+                id: *gid,
+                name: unix::find_group_name(*gid)?,
+            },
+            #[cfg(target_os = "macos")]
+            Qualifier::Guid(guid) => Principal::Guid(guid.to_string()),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::UserObj => Principal::Owner,
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::GroupObj => Principal::GroupOwner,
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::Mask => Principal::Mask,
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::Other => Principal::Other,
+            #[cfg(target_os = "freebsd")]
+            Qualifier::Everyone => Principal::Everyone,
+            Qualifier::Unknown(s) => Principal::Unknown(s.clone()),
+        };
+
+        Ok(principal)
+    }
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Principal::User { id, name } => write_id(f, "uid", *id, name.as_deref()),
+            Principal::Group { id, name } => write_id(f, "gid", *id, name.as_deref()),
+            Principal::Guid(guid) => write!(f, "guid={guid}"),
+            Principal::Unknown(s) => write!(f, "unknown={s}"),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Principal::Owner => f.write_str("owner"),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Principal::GroupOwner => f.write_str("group_owner"),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Principal::Mask => f.write_str("mask"),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Principal::Other => f.write_str("other"),
+            #[cfg(target_os = "freebsd")]
+            Principal::Everyone => f.write_str("everyone"),
+        }
+    }
+}
+
+/// Write "uid=1234(alice)" or "uid=1234".
+fn write_id(f: &mut fmt::Formatter, tag: &str, id: u32, name: Option<&str>) -> fmt::Result {
+    match name {
+        Some(name) => write!(f, "{tag}={id}({name})"),
+        None => write!(f, "{tag}={id}"),
+    }
+}
+
+impl FromStr for Principal {
+    type Err = format::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format::Error::Message(format!("invalid principal: {s:?}"));
+
+        let principal = match s {
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            "owner" => return Ok(Principal::Owner),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            "group_owner" => return Ok(Principal::GroupOwner),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            "mask" => return Ok(Principal::Mask),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            "other" => return Ok(Principal::Other),
+            #[cfg(target_os = "freebsd")]
+            "everyone" => return Ok(Principal::Everyone),
+            _ => s.split_once('=').ok_or_else(err)?,
+        };
+
+        let principal = match principal {
+            (tag @ ("uid" | "gid"), value) => {
+                let (id, name) = match value.split_once('(') {
+                    Some((id, rest)) => {
+                        let name = rest.strip_suffix(')').ok_or_else(err)?;
+                        (id, Some(name.to_string()))
+                    }
+                    None => (value, None),
+                };
+                let id = id.parse::<u32>().map_err(|_| err())?;
+                if tag == "uid" {
+                    Principal::User { id, name }
+                } else {
+                    Principal::Group { id, name }
+                }
+            }
+            ("guid", value) => Principal::Guid(value.to_string()),
+            ("unknown", value) => Principal::Unknown(value.to_string()),
+            _ => return Err(err()),
+        };
+
+        Ok(principal)
+    }
+}
+
+impl AclEntry {
+    /// Return the principal of the entry.
+    ///
+    /// The name is looked up to find the numeric id, then the id is looked
+    /// up to find out whether it has a name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the name is not a known user or group.
+    pub fn principal(&self) -> io::Result<Principal> {
+        if self.kind == AclEntryKind::Unknown {
+            return Ok(Principal::Unknown(self.name.clone()));
+        }
+        Principal::from_qualifier(&self.qualifier()?)
+    }
+}
+
+impl RawAclEntry {
+    /// Return the principal of the entry, looking up its name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn principal(&self) -> io::Result<Principal> {
+        Principal::from_qualifier(self.qualifier())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod principal_tests {
+    use super::*;
+    use crate::Perm;
+
+    #[test]
+    fn test_principal_text() {
+        let principals = [
+            (
+                Principal::User {
+                    id: 0,
+                    name: Some("root".to_string()),
+                },
+                "uid=0(root)",
+            ),
+            (
+                Principal::User {
+                    id: 1234,
+                    name: None,
+                },
+                "uid=1234",
+            ),
+            (
+                Principal::Group {
+                    id: 20,
+                    name: Some("a(b)".to_string()),
+                },
+                "gid=20(a(b))",
+            ),
+            (Principal::Group { id: 20, name: None }, "gid=20"),
+            (
+                Principal::Guid("ABCDEFAB-CDEF-ABCD-EFAB-CDEF00000001".to_string()),
+                "guid=ABCDEFAB-CDEF-ABCD-EFAB-CDEF00000001",
+            ),
+            (Principal::Unknown("@tag 42".to_string()), "unknown=@tag 42"),
+        ];
+
+        for (principal, text) in principals {
+            assert_eq!(principal.to_string(), text);
+            assert_eq!(text.parse::<Principal>().unwrap(), principal);
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        for (principal, text) in [
+            (Principal::Owner, "owner"),
+            (Principal::GroupOwner, "group_owner"),
+            (Principal::Mask, "mask"),
+            (Principal::Other, "other"),
+        ] {
+            assert_eq!(principal.to_string(), text);
+            assert_eq!(text.parse::<Principal>().unwrap(), principal);
+            assert_eq!(principal.to_entry_name(), "");
+        }
+
+        for text in ["", "1234", "uid=", "uid=x", "uid=1(a", "pid=1", "group"] {
+            let err = text.parse::<Principal>().unwrap_err();
+            assert_eq!(err.to_string(), format!("invalid principal: {text:?}"));
+        }
+    }
+
+    #[test]
+    fn test_principal_names() {
+        let principal = Principal::User {
+            id: 1234,
+            name: None,
+        };
+        assert_eq!(principal.id(), Some(1234));
+        assert_eq!(principal.resolved_name(), None);
+        assert_eq!(principal.to_entry_name(), "1234");
+    }
+
+    #[test]
+    fn test_aclentry_principal() -> io::Result<()> {
+        let entry = AclEntry::allow_user("1500", Perm::READ, None);
+        assert_eq!(
+            entry.principal()?,
+            Principal::User {
+                id: 1500,
+                name: None
+            }
+        );
+
+        let entry = AclEntry::allow_user("root", Perm::READ, None);
+        assert_eq!(entry.principal()?.to_string(), "uid=0(root)");
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        {
+            assert_eq!(
+                AclEntry::allow_user("", Perm::READ, None).principal()?,
+                Principal::Owner
+            );
+            assert_eq!(
+                AclEntry::allow_group("", Perm::READ, None).principal()?,
+                Principal::GroupOwner
+            );
+            assert_eq!(
+                AclEntry::allow_mask(Perm::READ, None).principal()?,
+                Principal::Mask
+            );
+            assert_eq!(
+                AclEntry::allow_other(Perm::READ, None).principal()?,
+                Principal::Other
+            );
+        }
+
+        assert!(
+            AclEntry::allow_user("non_existent_user_5f2a", Perm::READ, None)
+                .principal()
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_principal_serde() {
+        let principal = Principal::User {
+            id: 1234,
+            name: None,
+        };
+        let json = serde_json::to_string(&principal).unwrap();
+        assert_eq!(json, r#"{"user":{"id":1234,"name":null}}"#);
+        assert_eq!(serde_json::from_str::<Principal>(&json).unwrap(), principal);
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        {
+            let json = serde_json::to_string(&Principal::GroupOwner).unwrap();
+            assert_eq!(json, r#""group_owner""#);
+            assert_eq!(
+                serde_json::from_str::<Principal>(&json).unwrap(),
+                Principal::GroupOwner
+            );
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Implements a lazy iterator over ACL entries that defers name lookups.
+
+use crate::acl::{Acl, AclOption};
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::flag::Flag;
+use crate::names::NameCache;
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+use crate::util::*;
+
+use std::io;
+use std::path::Path;
+
+/// ACL entry as stored by the OS, before names are looked up.
+///
+/// Named users and groups are identified by their numeric uid or gid. Call
+/// [`RawAclEntry::name`] or [`RawAclEntry::to_entry`] to look up the name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawAclEntry {
+    /// Kind of entry (User, Group, Other, Mask, Everyone, or Unknown).
+    pub kind: AclEntryKind,
+
+    /// Permission bits for the entry.
+    pub perms: Perm,
+
+    /// Flags indicating whether an entry is inherited, etc.
+    pub flags: Flag,
+
+    /// True if entry is allowed; false means deny. Linux only supports
+    /// allow=true.
+    pub allow: bool,
+
+    qualifier: Qualifier,
+}
+
+impl RawAclEntry {
+    /// Return a `RawAclEntry` constructed from a native `acl_entry_t`.
+    pub(crate) fn from_raw(entry: acl_entry_t, acl: acl_t) -> io::Result<RawAclEntry> {
+        let (allow, qualifier, perms, flags) = xacl_get_entry(acl, entry)?;
+
+        let kind = match &qualifier {
+            Qualifier::Unknown(_) => AclEntryKind::Unknown,
+
+            #[cfg(target_os = "macos")]
+            Qualifier::User(_) | Qualifier::Guid(_) => AclEntryKind::User,
+
+            #[cfg(target_os = "macos")]
+            Qualifier::Group(_) => AclEntryKind::Group,
+
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::User(_) | Qualifier::UserObj => AclEntryKind::User,
+
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::Group(_) | Qualifier::GroupObj => AclEntryKind::Group,
+
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::Mask => AclEntryKind::Mask,
+
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            Qualifier::Other => AclEntryKind::Other,
+
+            #[cfg(target_os = "freebsd")]
+            Qualifier::Everyone => AclEntryKind::Everyone,
+        };
+
+        Ok(RawAclEntry {
+            kind,
+            perms,
+            flags,
+            allow,
+            qualifier,
+        })
+    }
+
+    /// Return the numeric uid or gid of a named user or group entry.
+    ///
+    /// Returns `None` for the file owner, the owning group, mask, other and
+    /// everyone entries, and for entries identified only by a GUID on macOS.
+    #[must_use]
+    pub fn id(&self) -> Option<u32> {
+        match self.qualifier {
+            Qualifier::User(uid) => Some(uid),
+            Qualifier::Group(gid) => Some(gid),
+            _ => None,
+        }
+    }
+
+    /// Return the qualifier of the entry.
+    pub(crate) const fn qualifier(&self) -> &Qualifier {
+        &self.qualifier
+    }
+
+    /// Look up the name of the entry's user or group.
+    ///
+    /// The name is the same as [`AclEntry::name`]. If the uid or gid has no
+    /// name, the name is the number as a string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn name(&self) -> io::Result<String> {
+        self.qualifier.name()
+    }
+
+    /// Look up the name and return an [`AclEntry`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn to_entry(&self) -> io::Result<AclEntry> {
+        self.to_entry_cached(None)
+    }
+
+    /// Look up the name through `names` if provided, and return an
+    /// [`AclEntry`].
+    pub(crate) fn to_entry_cached(&self, names: Option<&NameCache>) -> io::Result<AclEntry> {
+        let name = match names {
+            Some(cache) => cache.name(&self.qualifier)?,
+            None => self.qualifier.name()?,
+        };
+
+        Ok(AclEntry {
+            kind: self.kind,
+            name,
+            perms: self.perms,
+            flags: self.flags,
+            allow: self.allow,
+        })
+    }
+}
+
+/// ACL of a file, read by [`getfacl_raw`].
+///
+/// Use [`RawAcl::entries`] to iterate over the entries. Names are only
+/// looked up when asked for.
+pub struct RawAcl {
+    acls: Vec<Acl>,
+}
+
+impl RawAcl {
+    /// Return an iterator over the entries of the ACL.
+    ///
+    /// On Linux and `FreeBSD`, the access ACL entries come first, followed by
+    /// the default ACL entries with the [`Flag::DEFAULT`] flag set.
+    pub fn entries(&mut self) -> RawEntries<'_> {
+        RawEntries {
+            acls: self.acls.iter_mut(),
+            current: None,
+            first: true,
+        }
+    }
+}
+
+/// Iterator over the entries of a [`RawAcl`].
+///
+/// The position of the iterator is stored in the native ACL, so the iterator
+/// borrows the [`RawAcl`] mutably.
+pub struct RawEntries<'a> {
+    acls: std::slice::IterMut<'a, Acl>,
+    current: Option<&'a mut Acl>,
+    first: bool,
+}
+
+impl Iterator for RawEntries<'_> {
+    type Item = io::Result<RawAclEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.current.as_mut() {
+                Some(acl) => {
+                    if let Some(item) = acl.next_raw_entry(self.first) {
+                        self.first = false;
+                        return Some(item);
+                    }
+                    self.current = None;
+                }
+                None => {
+                    self.current = Some(self.acls.next()?);
+                    self.first = true;
+                }
+            }
+        }
+    }
+}
+
+/// Get the access control list (ACL) for a file or directory, without
+/// looking up names.
+///
+/// Takes the same options as [`getfacl`](crate::getfacl). The entries are
+/// decoded one at a time as the iterator advances, and user and group names
+/// are only looked up by [`RawAclEntry::name`] or [`RawAclEntry::to_entry`].
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::{getfacl_raw, Perm};
+///
+/// let mut acl = getfacl_raw("./tmp/foo", None)?;
+/// let mut writable = false;
+/// for entry in acl.entries() {
+///     writable |= entry?.perms.contains(Perm::WRITE);
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn getfacl_raw<P, O>(path: P, options: O) -> io::Result<RawAcl>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let acls = crate::read_acls(path.as_ref(), options.into().unwrap_or_default())?;
+    Ok(RawAcl { acls })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, target_os = "linux"))]
+mod raw_tests {
+    use super::*;
+    use crate::{from_mode, getfacl, setfacl};
+
+    #[test]
+    fn test_getfacl_raw() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut entries = from_mode(0o750);
+        entries.push(AclEntry::allow_user("500", Perm::READ, None));
+        entries.push(AclEntry::allow_mask(Perm::READ, None));
+        entries.push(AclEntry::allow_group("", Perm::READ, Flag::DEFAULT));
+        entries.push(AclEntry::allow_user("", Perm::READ, Flag::DEFAULT));
+        entries.push(AclEntry::allow_other(Perm::empty(), Flag::DEFAULT));
+        setfacl(&[&dir], &entries, None)?;
+
+        let mut acl = getfacl_raw(&dir, None)?;
+        let raw = acl.entries().collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(raw.len(), 8);
+        let named = raw.iter().find(|entry| entry.id().is_some()).unwrap();
+        assert_eq!(named.kind, AclEntryKind::User);
+        assert_eq!(named.id(), Some(500));
+        assert_eq!(named.name()?, "500");
+        assert!(raw[5..].iter().all(|entry| entry.flags == Flag::DEFAULT));
+
+        let resolved = raw
+            .iter()
+            .map(RawAclEntry::to_entry)
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(resolved, getfacl(&dir, None)?);
+
+        // Iterating again starts from the first entry.
+        assert_eq!(acl.entries().count(), 8);
+        let mut acl = getfacl_raw(&dir, AclOption::DEFAULT_ACL)?;
+        assert_eq!(acl.entries().count(), 3);
+
+        Ok(())
+    }
+}
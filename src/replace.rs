@@ -0,0 +1,330 @@
+//! Implements replacement of one user or group by another in ACL's.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::failx::fail_custom;
+use crate::policy::effective_perms;
+use crate::principal::Principal;
+use crate::qualifier::Qualifier;
+use crate::walk::Walk;
+use crate::{getfacl, setfacl};
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Rewrite the entries of a file's ACL for `from` into entries for `to`.
+///
+/// Both access and default entries are rewritten. Entries match `from` by
+/// uid or gid, so `uid=1234` matches an entry named `alice` if alice's uid
+/// is 1234. If the ACL already has an entry for `to` with the same flags and
+/// type, the effective permissions of the rewritten entry are added to it,
+/// unless a deny entry for `to` sits between the two. The mask is left as it
+/// is, so no other entry gains permissions.
+///
+/// Returns the entries for `from` as they were before the change. The ACL is
+/// only written if there are any.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure, or if `from` or `to` is not a user
+/// or group.
+pub fn replace_principal<P: AsRef<Path>>(
+    path: P,
+    from: &Principal,
+    to: &Principal,
+) -> io::Result<Vec<AclEntry>> {
+    let replacement = Replacement::new(from, to)?;
+    replacement.apply(path.as_ref())
+}
+
+/// Walk the tree at `root` and rewrite the entries for `from` into entries
+/// for `to`, as in [`replace_principal`].
+///
+/// Returns the rewritten entries of each file that had any. Symlinks are not
+/// followed.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::{replace_principal_tree, Principal};
+///
+/// let from: Principal = "gid=1001".parse()?;
+/// let to: Principal = "gid=1002".parse()?;
+/// for (path, entries) in replace_principal_tree("/srv/shared", &from, &to)? {
+///     println!("{}: {} entries", path.display(), entries.len());
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. Files before the failing one have
+/// already been rewritten.
+pub fn replace_principal_tree<P: AsRef<Path>>(
+    root: P,
+    from: &Principal,
+    to: &Principal,
+) -> io::Result<Vec<(PathBuf, Vec<AclEntry>)>> {
+    let replacement = Replacement::new(from, to)?;
+    let mut result = Vec::new();
+    for item in Walk::new(root.as_ref()) {
+        let entry = item?;
+        if entry.metadata.is_symlink() {
+            continue;
+        }
+
+        let replaced = replacement.apply(&entry.path)?;
+        if !replaced.is_empty() {
+            result.push((entry.path, replaced));
+        }
+    }
+    Ok(result)
+}
+
+/// Kind and id of the entries to replace, and the kind and name of the
+/// entries that replace them.
+struct Replacement {
+    from: (AclEntryKind, u32),
+    to: (AclEntryKind, String),
+}
+
+impl Replacement {
+    fn new(from: &Principal, to: &Principal) -> io::Result<Replacement> {
+        let (Some(from_kind), Some(from_id)) = (from.entry_kind(), from.id()) else {
+            return fail_custom(&format!("invalid principal: {:?}", from.to_string()));
+        };
+        let Some(to_key) = entry_key(to)? else {
+            return fail_custom(&format!("invalid principal: {:?}", to.to_string()));
+        };
+
+        Ok(Replacement {
+            from: (from_kind, from_id),
+            to: to_key,
+        })
+    }
+
+    /// Return true if `entry` is for the principal being replaced.
+    fn matches(&self, entry: &AclEntry) -> io::Result<bool> {
+        let (kind, id) = self.from;
+        if entry.kind != kind || entry.name.is_empty() {
+            return Ok(false);
+        }
+        Ok(entry.principal()?.id() == Some(id))
+    }
+
+    /// Rewrite the ACL of the file at `path`.
+    fn apply(&self, path: &Path) -> io::Result<Vec<AclEntry>> {
+        let mut entries = getfacl(path, None)?;
+        let mut replaced = Vec::new();
+
+        // Go backwards, so that removing a merged entry does not move the
+        // entries still to visit.
+        for index in (0..entries.len()).rev() {
+            if self.matches(&entries[index])? {
+                replaced.push(entries[index].clone());
+                reassign_entry(&mut entries, index, &self.to);
+            }
+        }
+        replaced.reverse();
+
+        if !replaced.is_empty() {
+            setfacl(&[path], &entries, None)?;
+        }
+
+        Ok(replaced)
+    }
+}
+
+/// Give `entries[index]` to the user or group with the kind and name in
+/// `to`.
+///
+/// `to` must use the name that `getfacl` returns, as from [`entry_key`]. If
+/// there is already an entry for `to` with the same flags and type, and no
+/// deny entry for `to` between the two, the effective permissions of the
+/// entry are added to it and the entry is removed. Otherwise, the entry is
+/// rewritten in place.
+pub(crate) fn reassign_entry(
+    entries: &mut Vec<AclEntry>,
+    index: usize,
+    to: &(AclEntryKind, String),
+) {
+    let (kind, name) = to;
+    let is_target = |item: &AclEntry| item.kind == *kind && item.name == *name;
+    let (flags, allow) = (entries[index].flags, entries[index].allow);
+
+    let existing = entries.iter().enumerate().position(|(i, item)| {
+        if i == index || !is_target(item) || item.flags != flags || item.allow != allow {
+            return false;
+        }
+        let between = &entries[i.min(index) + 1..i.max(index)];
+        !between.iter().any(|item| is_target(item) && !item.allow)
+    });
+
+    match existing {
+        Some(target) => {
+            let perms = effective_perms(&entries[index], entries);
+            entries[target].perms |= perms;
+            entries.remove(index);
+        }
+        None => {
+            entries[index].kind = *kind;
+            entries[index].name.clone_from(name);
+        }
+    }
+}
+
+/// Return the kind and name of the entries for a user or group, or `None`
+/// for other principals.
+///
+/// The name is the one the id has on this system, as `getfacl` returns it.
+pub(crate) fn entry_key(principal: &Principal) -> io::Result<Option<(AclEntryKind, String)>> {
+    let qualifier = match (principal.entry_kind(), principal.id()) {
+        (Some(AclEntryKind::User), Some(uid)) => Qualifier::User(uid),
+        (Some(AclEntryKind::Group), Some(gid)) => Qualifier::Group(gid),
+        _ => return Ok(None),
+    };
+    let resolved = Principal::from_qualifier(&qualifier)?;
+    Ok(resolved
+        .entry_kind()
+        .map(|kind| (kind, resolved.to_entry_name())))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, target_os = "linux"))]
+mod replace_tests {
+    use super::*;
+    use crate::{from_mode, Flag, Perm};
+    use std::fs;
+
+    #[test]
+    fn test_reassign_entry() {
+        let to = (AclEntryKind::User, "500".to_string());
+        let mut entries = vec![
+            AclEntry::allow_user("500", Perm::READ, None),
+            AclEntry::allow_user("501", Perm::WRITE | Perm::EXECUTE, None),
+            AclEntry::allow_user("502", Perm::WRITE, Flag::DEFAULT),
+            AclEntry::allow_mask(Perm::READ | Perm::WRITE, None),
+        ];
+
+        // Only the effective permissions are merged; the mask is unchanged.
+        reassign_entry(&mut entries, 1, &to);
+        assert_eq!(
+            entries[0],
+            AclEntry::allow_user("500", Perm::READ | Perm::WRITE, None)
+        );
+        assert_eq!(entries.len(), 3);
+
+        // No entry with the same flags, so the entry is rewritten in place.
+        reassign_entry(&mut entries, 1, &to);
+        assert_eq!(
+            entries[1],
+            AclEntry::allow_user("500", Perm::WRITE, Flag::DEFAULT)
+        );
+        assert_eq!(
+            entries[2],
+            AclEntry::allow_mask(Perm::READ | Perm::WRITE, None)
+        );
+    }
+
+    #[test]
+    fn test_reassign_entry_deny() {
+        let to = (AclEntryKind::User, "500".to_string());
+        let mut entries = vec![
+            AclEntry::allow_user("500", Perm::READ, None),
+            AclEntry {
+                allow: false,
+                ..AclEntry::allow_user("500", Perm::WRITE, None)
+            },
+            AclEntry::allow_user("501", Perm::WRITE, None),
+        ];
+
+        // Merging would move the write permission ahead of the deny entry.
+        reassign_entry(&mut entries, 2, &to);
+        assert_eq!(entries[0], AclEntry::allow_user("500", Perm::READ, None));
+        assert_eq!(entries[2], AclEntry::allow_user("500", Perm::WRITE, None));
+    }
+
+    #[test]
+    fn test_replace_principal_tree() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        fs::write(&file, "data")?;
+
+        let mut entries = from_mode(0o750);
+        entries.push(AclEntry::allow_group("1501", Perm::READ, None));
+        entries.push(AclEntry::allow_group("1502", Perm::EXECUTE, None));
+        entries.push(AclEntry::allow_mask(Perm::READ, None));
+        entries.extend(from_mode(0o750).into_iter().map(|entry| AclEntry {
+            flags: Flag::DEFAULT,
+            ..entry
+        }));
+        entries.push(AclEntry::allow_group("1501", Perm::WRITE, Flag::DEFAULT));
+        setfacl(&[dir.path()], &entries, None)?;
+        setfacl(&[&file], &from_mode(0o640), None)?;
+
+        let from = "gid=1501".parse()?;
+        let to = "gid=1502".parse()?;
+        let replaced = replace_principal_tree(&dir, &from, &to)?;
+        assert_eq!(
+            replaced,
+            vec![(
+                dir.path().to_path_buf(),
+                vec![entries[3].clone(), entries[9].clone()]
+            )]
+        );
+
+        // The access entries are merged and the masks are kept, so gid 1502
+        // still has no effective execute permission.
+        let result = getfacl(&dir, None)?;
+        let named = result
+            .iter()
+            .filter(|entry| !entry.name.is_empty())
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            named,
+            vec![
+                AclEntry::allow_group("1502", Perm::READ | Perm::EXECUTE, None),
+                AclEntry::allow_group("1502", Perm::WRITE, Flag::DEFAULT),
+            ]
+        );
+        assert!(result.contains(&AclEntry::allow_mask(Perm::READ, None)));
+        assert!(result.contains(&AclEntry::allow_mask(
+            Perm::READ | Perm::WRITE | Perm::EXECUTE,
+            Flag::DEFAULT
+        )));
+
+        // Nothing left to replace.
+        assert!(replace_principal(&dir, &from, &to)?.is_empty());
+
+        let err = replace_principal(&dir, &Principal::Owner, &to).unwrap_err();
+        assert_eq!(err.to_string(), "invalid principal: \"owner\"");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_principal_resolved() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let mut entries = from_mode(0o640);
+        entries.push(AclEntry::allow_group("root", Perm::READ, None));
+        entries.push(AclEntry::allow_group("1501", Perm::WRITE, None));
+        entries.push(AclEntry::allow_mask(Perm::READ | Perm::WRITE, None));
+        setfacl(&[&file], &entries, None)?;
+
+        // gid 0 is merged into the existing entry for "root".
+        let from = "gid=1501".parse()?;
+        let to = "gid=0".parse()?;
+        assert_eq!(replace_principal(&file, &from, &to)?, [entries[4].clone()]);
+        let result = getfacl(&file, None)?;
+        assert!(result.contains(&AclEntry::allow_group(
+            "root",
+            Perm::READ | Perm::WRITE,
+            None
+        )));
+        assert_eq!(result.len(), entries.len() - 1);
+
+        Ok(())
+    }
+}
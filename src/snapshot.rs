@@ -0,0 +1,283 @@
+//! Implements tree snapshot and restore using a JSON Lines manifest.
+
+use crate::failx::{custom_err, fail_custom, path_err};
+use crate::fileacl::FileAcl;
+use crate::walk::Walk;
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Path used in the manifest for the root of the tree.
+const ROOT_PATH: &str = ".";
+
+/// Write a manifest of the ACLs of every file in a directory tree.
+///
+/// The manifest is written in [JSON Lines](https://jsonlines.org) format: one
+/// [`FileAcl`] record per line, with the path relative to `root`. The root
+/// itself is recorded as `"."`. Files are visited in sorted order, so taking
+/// two snapshots of an unchanged tree produces identical output.
+///
+/// Records are written as the tree is walked; the manifest is never held in
+/// memory. Symlinks are not followed and have no record. A file that is
+/// removed while the tree is walked has no record. Paths that are not valid
+/// UTF-8 are written escaped, as described in [`FileAcl`].
+///
+/// Returns the number of records written.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let file = std::fs::File::create("./acls.jsonl")?;
+/// exacl::snapshot("./tmp", std::io::BufWriter::new(file))?;
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn snapshot<P, W>(root: P, mut writer: W) -> io::Result<usize>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    let mut count = 0;
+
+    for (i, item) in Walk::new(root.as_ref()).enumerate() {
+        // Below the root, skip files removed during the walk.
+        let result = item.and_then(|entry| {
+            if entry.metadata.is_symlink() {
+                return Ok(None);
+            }
+            let record = FileAcl::read(&entry.path, None)?;
+            Ok(Some((entry, record)))
+        });
+        let (entry, mut record) = match result {
+            Ok(Some(found)) => found,
+            Ok(None) => continue,
+            Err(err) if i > 0 && err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+
+        record.path = if entry.relative.as_os_str().is_empty() {
+            PathBuf::from(ROOT_PATH)
+        } else {
+            entry.relative
+        };
+
+        serde_json::to_writer(&mut writer, &record)?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Restore ACLs from a manifest written by [`snapshot`].
+///
+/// Each record's path is interpreted relative to `root`. If `prefix` is
+/// given, only records whose relative path starts with `prefix` are
+/// restored; the comparison is by whole path components. Each record is
+/// applied with [`FileAcl::apply`], which restores ownership, mode and ACL.
+///
+/// A record whose path is absolute or contains `..`, or whose path goes
+/// through a symlink below `root`, is an error. Symlinks are not followed.
+///
+/// The manifest is read one line at a time. Blank lines are ignored.
+///
+/// Returns the number of records restored.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. Restoring stops at the first error;
+/// records before it have already been applied.
+pub fn restore<P, R>(root: P, reader: R, prefix: Option<&Path>) -> io::Result<usize>
+where
+    P: AsRef<Path>,
+    R: io::Read,
+{
+    // Symlinks in `root` itself are trusted.
+    let root = fs::canonicalize(root.as_ref()).map_err(|err| path_err(root.as_ref(), &err))?;
+    let mut count = 0;
+
+    for (i, line_result) in io::BufReader::new(reader).lines().enumerate() {
+        let line = line_result?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record = serde_json::from_str::<FileAcl>(&line)
+            .map_err(|err| custom_err(&format!("line {}", i + 1), &err.into()))?;
+
+        if let Some(prefix) = prefix {
+            if !record.path.starts_with(prefix) {
+                continue;
+            }
+        }
+
+        let path = record_path(&root, &record.path)
+            .map_err(|err| custom_err(&format!("line {}", i + 1), &err))?;
+        record.apply(path)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Return the path of a record under `root`.
+///
+/// The record's path must stay inside `root`, and must not go through a
+/// symlink below `root`.
+fn record_path(root: &Path, relative: &Path) -> io::Result<PathBuf> {
+    let escapes = relative.components().any(|component| {
+        matches!(
+            component,
+            Component::RootDir | Component::Prefix(_) | Component::ParentDir
+        )
+    });
+    if escapes {
+        return fail_custom(&format!("invalid path {relative:?}"));
+    }
+
+    let mut path = root.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        path.push(component);
+        let metadata = fs::symlink_metadata(&path).map_err(|err| path_err(&path, &err))?;
+        if components.peek().is_some() && metadata.is_symlink() {
+            return fail_custom(&format!("refusing to follow symlink {path:?}"));
+        }
+    }
+
+    Ok(path)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, any(target_os = "linux", target_os = "freebsd")))]
+mod snapshot_tests {
+    use super::*;
+    use crate::{getfacl, setfacl, AclEntry, Perm};
+    use std::fs;
+
+    #[test]
+    fn test_snapshot_restore() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("sub"))?;
+        fs::write(dir.path().join("sub/file"), "")?;
+        std::os::unix::fs::symlink("sub", dir.path().join("link"))?;
+
+        let file = dir.path().join("sub/file");
+        let mut entries = getfacl(&file, None)?;
+        entries.push(AclEntry::allow_user("500", Perm::READ, None));
+        setfacl(&[&file], &entries, None)?;
+        let expected = getfacl(&file, None)?;
+
+        let mut manifest = Vec::new();
+        assert_eq!(snapshot(dir.path(), &mut manifest)?, 3);
+
+        let text = String::from_utf8(manifest.clone()).unwrap();
+        let paths = text
+            .lines()
+            .map(|line| serde_json::from_str::<FileAcl>(line).unwrap().path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, [".", "sub", "sub/file"].map(PathBuf::from));
+
+        // Snapshot of an unchanged tree is identical.
+        let mut again = Vec::new();
+        snapshot(dir.path(), &mut again)?;
+        assert_eq!(manifest, again);
+
+        // Change the ACL, then restore only the `sub/file` record.
+        setfacl(&[&file], &crate::from_mode(0o600), None)?;
+        let count = restore(dir.path(), &manifest[..], Some(Path::new("sub/file")))?;
+        assert_eq!(count, 1);
+        assert_eq!(getfacl(&file, None)?, expected);
+
+        // A prefix matches whole components only.
+        let count = restore(dir.path(), &manifest[..], Some(Path::new("su")))?;
+        assert_eq!(count, 0);
+
+        let count = restore(dir.path(), &manifest[..], None)?;
+        assert_eq!(count, 3);
+
+        let err = restore(dir.path(), &b"\n{}\n"[..], None).unwrap_err();
+        assert!(err.to_string().starts_with("line 2: missing field"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_snapshot_non_utf8() -> io::Result<()> {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir()?;
+        let name = OsStr::from_bytes(b"a\xff");
+        fs::write(dir.path().join(name), "")?;
+
+        let mut manifest = Vec::new();
+        assert_eq!(snapshot(dir.path(), &mut manifest)?, 2);
+        let text = String::from_utf8(manifest.clone()).unwrap();
+        assert!(text.contains(r#""path":"a\\377""#));
+
+        assert_eq!(
+            restore(dir.path(), &manifest[..], Some(Path::new(name)))?,
+            1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_outside_root() -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        let victim = dir.path().join("victim");
+        fs::create_dir(&root)?;
+        fs::create_dir(root.join("sub"))?;
+        fs::write(root.join("file"), "")?;
+        fs::write(&victim, "")?;
+        fs::set_permissions(&victim, fs::Permissions::from_mode(0o644))?;
+
+        let mut record = FileAcl::read(root.join("file"), None)?;
+        record.mode = 0o755;
+        record.access = crate::from_mode(0o755);
+        let manifest = |path: &Path| {
+            let mut record = record.clone();
+            record.path = path.to_path_buf();
+            serde_json::to_vec(&record).unwrap()
+        };
+
+        let paths = [
+            Path::new("../victim"),
+            &victim,
+            Path::new("sub/../../victim"),
+        ];
+        for path in paths {
+            let err = restore(&root, &manifest(path)[..], None).unwrap_err();
+            assert_eq!(err.to_string(), format!("line 1: invalid path {path:?}"));
+        }
+
+        // A symlink planted in the tree is not followed.
+        std::os::unix::fs::symlink(&victim, root.join("link"))?;
+        std::os::unix::fs::symlink(dir.path(), root.join("dirlink"))?;
+        for path in ["link", "dirlink/victim"] {
+            assert!(restore(&root, &manifest(Path::new(path))[..], None).is_err());
+        }
+        assert_eq!(fs::metadata(&victim)?.permissions().mode() & 0o777, 0o644);
+
+        // A file record is not applied to a directory.
+        let err = restore(&root, &manifest(Path::new("sub"))[..], None).unwrap_err();
+        assert!(err.to_string().ends_with("expected file, found directory"));
+
+        assert_eq!(restore(&root, &manifest(Path::new("file"))[..], None)?, 1);
+
+        Ok(())
+    }
+}
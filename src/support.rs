@@ -0,0 +1,162 @@
+//! Implements detection of file system ACL support.
+
+use crate::failx::path_err;
+use crate::util::{xacl_acl_support, xacl_has_extended};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Kind of ACL supported by a file system.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum AclSupport {
+    /// The file system does not support ACL's. Only the mode bits apply.
+    None,
+
+    /// The file system supports POSIX.1e ACL's.
+    Posix,
+
+    /// The file system supports NFSv4-style ACL's.
+    Nfs4,
+}
+
+impl fmt::Display for AclSupport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AclSupport::None => "none",
+            AclSupport::Posix => "posix",
+            AclSupport::Nfs4 => "nfs4",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Return the kind of ACL supported by the file system that holds `path`.
+///
+/// Use this to decide whether to set an ACL, or to fall back to the mode
+/// bits, before changing anything. Symlinks are followed.
+///
+/// # Platform
+///
+/// On Linux, ACL's are stored in extended attributes. `acl_support` probes
+/// for the `system.posix_acl_access` and `system.nfs4_acl` attributes.
+/// A file system mounted without ACL support, or one that does not support
+/// extended attributes, returns [`AclSupport::None`].
+///
+/// On FreeBSD, `acl_support` uses `pathconf` with `_PC_ACL_NFS4` and
+/// `_PC_ACL_EXTENDED`.
+///
+/// On macOS, `acl_support` uses `pathconf` with `_PC_EXTENDED_SECURITY_NP`.
+/// macOS extended ACL's are reported as [`AclSupport::Nfs4`].
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::{acl_support, AclSupport};
+///
+/// if acl_support("./tmp/foo")? == AclSupport::None {
+///     // Set the mode bits instead.
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn acl_support<P: AsRef<Path>>(path: P) -> io::Result<AclSupport> {
+    let path = path.as_ref();
+    xacl_acl_support(path).map_err(|err| path_err(path, &err))
+}
+
+/// Return true if a file has an ACL beyond its mode bits.
+///
+/// This is the check behind the `+` marker in `ls -l`. It is much cheaper
+/// than [`getfacl`](crate::getfacl): no entries are decoded and no names
+/// are looked up. Symlinks are followed.
+///
+/// # Platform
+///
+/// On Linux, only the sizes of the `system.posix_acl_access` and
+/// `system.posix_acl_default` extended attributes are read. A file has an
+/// extended ACL if its access ACL has more than the three base entries, or
+/// if it has a default ACL. On NFS mounts, the `system.nfs4_acl` attribute
+/// is read too. An NFSv4 ACL is extended if it has an entry for a principal
+/// other than `OWNER@`, `GROUP@` and `EVERYONE@`, or an inheritable entry.
+///
+/// On FreeBSD, a file has an extended ACL if `acl_is_trivial_np` reports a
+/// non-trivial ACL, or if a directory has a default ACL.
+///
+/// On macOS, a file has an extended ACL if its ACL has any entries.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn has_extended_acl<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let path = path.as_ref();
+    xacl_has_extended(path).map_err(|err| path_err(path, &err))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod support_tests {
+    use super::*;
+
+    #[test]
+    fn test_acl_support() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        assert_ne!(acl_support(&file)?, AclSupport::None);
+
+        #[cfg(target_os = "linux")]
+        assert_eq!(acl_support("/proc/self")?, AclSupport::None);
+
+        let missing = file.path().with_extension("missing");
+        let err = acl_support(&missing).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with(&format!("File {missing:?}: ")));
+
+        assert_eq!(AclSupport::Posix.to_string(), "posix");
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_has_extended_acl() -> io::Result<()> {
+        use crate::{from_mode, setfacl, AclEntry, Flag, Perm};
+
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        std::fs::write(&file, "")?;
+        assert!(!has_extended_acl(&file)?);
+        assert!(!has_extended_acl(&dir)?);
+
+        let mut entries = from_mode(0o640);
+        entries.push(AclEntry::allow_user("500", Perm::READ, None));
+        setfacl(&[&file], &entries, None)?;
+        assert!(has_extended_acl(&file)?);
+
+        // Setting the base entries again removes the extended ACL.
+        setfacl(&[&file], &from_mode(0o640), None)?;
+        assert!(!has_extended_acl(&file)?);
+
+        let mut entries = from_mode(0o750);
+        entries.extend(from_mode(0o750).into_iter().map(|entry| AclEntry {
+            flags: Flag::DEFAULT,
+            ..entry
+        }));
+        setfacl(&[&dir], &entries, None)?;
+        assert!(has_extended_acl(&dir)?);
+
+        assert!(has_extended_acl(dir.path().join("missing")).is_err());
+
+        Ok(())
+    }
+}
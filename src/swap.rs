@@ -0,0 +1,211 @@
+//! Implements compare-and-swap ACL updates.
+
+use crate::acl::AclOption;
+use crate::aclentry::AclEntry;
+use crate::diff::{diff, AclDiff};
+use crate::{getfacl, setfacl};
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Error returned when an ACL changed since it was read.
+///
+/// [`setfacl_if_unchanged`] returns an [`io::Error`] that wraps an
+/// `AclMismatch`. Use [`AclMismatch::from_error`] to retrieve it.
+#[derive(Debug)]
+pub struct AclMismatch {
+    /// Path of the file.
+    pub path: PathBuf,
+
+    /// Difference between the expected ACL and the current ACL.
+    pub diff: AclDiff,
+}
+
+impl AclMismatch {
+    /// Return the mismatch wrapped by an error from [`setfacl_if_unchanged`].
+    #[must_use]
+    pub fn from_error(err: &io::Error) -> Option<&AclMismatch> {
+        err.get_ref()?.downcast_ref::<AclMismatch>()
+    }
+}
+
+impl fmt::Display for AclMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "File {:?}: ACL changed since it was read", self.path)
+    }
+}
+
+impl Error for AclMismatch {}
+
+/// Set the ACL of a file only if its current ACL equals `expected`.
+///
+/// Use this to apply a `getfacl` → modify → `setfacl` cycle without
+/// overwriting a change made by someone else in between. `expected` is
+/// the list of entries read earlier; the order of entries does not matter.
+/// If the current ACL differs, nothing is written and the error wraps an
+/// [`AclMismatch`].
+///
+/// # Linux
+///
+/// The file is opened first, and the ACL is read and written through the
+/// open file. If the path is renamed or replaced in between, the update
+/// still applies to the file that was checked. The check and the write are
+/// separate system calls, so a conflicting write can still slip in between
+/// them; the window is just very small.
+///
+/// On macOS and FreeBSD, the ACL is read and written by path.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::{getfacl, setfacl_if_unchanged, AclEntry, AclMismatch, Perm};
+///
+/// let expected = getfacl("./tmp/foo", None)?;
+/// let mut entries = expected.clone();
+/// entries.push(AclEntry::allow_user("some_user", Perm::READ, None));
+///
+/// if let Err(err) = setfacl_if_unchanged("./tmp/foo", &expected, &entries, None) {
+///     if AclMismatch::from_error(&err).is_some() {
+///         // Read the ACL again and retry.
+///     }
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn setfacl_if_unchanged<P, O>(
+    path: P,
+    expected: &[AclEntry],
+    entries: &[AclEntry],
+    options: O,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let path = path.as_ref();
+    let options = options.into().unwrap_or_default();
+
+    with_open_path(path, options, |open_path, options| {
+        let current = getfacl(open_path, options)?;
+        let changes = diff(expected, &current);
+        if !changes.is_empty() {
+            return Ok(Err(io::Error::other(AclMismatch {
+                path: path.to_path_buf(),
+                diff: changes,
+            })));
+        }
+
+        setfacl(&[open_path], entries, options).map(Ok)
+    })?
+}
+
+/// Open `path` and call `func` with a path that refers to the open file.
+///
+/// With [`AclOption::NO_FOLLOW`], no component of `path` may be a symlink.
+/// `func` gets the options without `NO_FOLLOW`, since the path of the open
+/// file is itself a symlink. Errors returned by `func` mention `path` rather
+/// than the path of the open file.
+#[cfg(target_os = "linux")]
+pub(crate) fn with_open_path<T, F>(path: &Path, options: AclOption, func: F) -> io::Result<T>
+where
+    F: FnOnce(&Path, AclOption) -> io::Result<T>,
+{
+    use crate::at::with_fd_path;
+    use crate::failx::path_err;
+    use crate::nofollow::with_path_no_follow;
+    use crate::sys::sg;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if options.contains(AclOption::NO_FOLLOW) {
+        let options = options - AclOption::NO_FOLLOW;
+        return with_path_no_follow(path, |open_path| func(open_path, options));
+    }
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(sg::O_PATH)
+        .open(path)
+        .map_err(|err| path_err(path, &err))?;
+
+    with_fd_path(&file, path, |open_path| func(open_path, options))
+}
+
+/// Open `path` and call `func` with a path that refers to the open file.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn with_open_path<T, F>(path: &Path, options: AclOption, func: F) -> io::Result<T>
+where
+    F: FnOnce(&Path, AclOption) -> io::Result<T>,
+{
+    func(path, options)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, target_os = "linux"))]
+mod swap_tests {
+    use super::*;
+    use crate::nofollow::SymlinkError;
+    use crate::{from_mode, Perm};
+
+    #[test]
+    fn test_setfacl_if_unchanged() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        setfacl(&[&file], &from_mode(0o600), None)?;
+
+        let expected = getfacl(&file, None)?;
+        let mut entries = expected.clone();
+        entries.push(AclEntry::allow_user("500", Perm::READ, None));
+
+        // Someone else changes the ACL.
+        setfacl(&[&file], &from_mode(0o640), None)?;
+
+        let err = setfacl_if_unchanged(&file, &expected, &entries, None).unwrap_err();
+        let mismatch = AclMismatch::from_error(&err).unwrap();
+        assert_eq!(mismatch.path, file.path());
+        assert_eq!(mismatch.diff.to_string(), "~ allow::group:: -> read\n");
+        assert_eq!(getfacl(&file, None)?, from_mode(0o640));
+
+        let expected = getfacl(&file, None)?;
+        setfacl_if_unchanged(&file, &expected, &entries, None)?;
+        assert_eq!(getfacl(&file, None)?.len(), 5);
+
+        // Errors mention the original path.
+        let err = setfacl_if_unchanged(&file, &[], &[], AclOption::DEFAULT_ACL).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("File {:?}: Permission denied (os error 13)", file.path())
+        );
+        let missing = file.path().with_extension("missing");
+        let err = setfacl_if_unchanged(&missing, &[], &[], None).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("File {missing:?}: ")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_setfacl_if_unchanged_no_follow() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        let link = dir.path().join("link");
+        std::fs::write(&file, "")?;
+        std::os::unix::fs::symlink("file", &link)?;
+
+        let expected = getfacl(&file, None)?;
+        let entries = from_mode(0o604);
+        setfacl_if_unchanged(&file, &expected, &entries, AclOption::NO_FOLLOW)?;
+        assert_eq!(getfacl(&file, None)?, entries);
+
+        let err =
+            setfacl_if_unchanged(&link, &entries, &expected, AclOption::NO_FOLLOW).unwrap_err();
+        assert_eq!(SymlinkError::from_error(&err).unwrap().link, link);
+        assert_eq!(getfacl(&file, None)?, entries);
+
+        Ok(())
+    }
+}
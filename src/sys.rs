@@ -131,6 +131,12 @@ pub mod sg {
     pub const ENOMEM: i32 = super::ENOMEM as i32;
     pub const ERANGE: i32 = super::ERANGE as i32;
     pub const ACL_MAX_ENTRIES: i32 = super::ACL_MAX_ENTRIES as i32;
+    #[cfg(target_os = "linux")]
+    pub const O_PATH: i32 = super::O_PATH as i32;
+    #[cfg(target_os = "linux")]
+    pub const ENODATA: i32 = super::ENODATA as i32;
+    #[cfg(target_os = "linux")]
+    pub const O_NOFOLLOW: i32 = super::O_NOFOLLOW as i32;
 
     #[cfg(target_os = "macos")]
     pub const ACL_TYPE_EXTENDED: acl_type_t = super::acl_type_t_ACL_TYPE_EXTENDED;
@@ -192,6 +198,10 @@ pub mod sg {
 
     #[cfg(target_os = "freebsd")]
     pub const PC_ACL_NFS4: i32 = super::_PC_ACL_NFS4 as i32;
+    #[cfg(target_os = "freebsd")]
+    pub const PC_ACL_EXTENDED: i32 = super::_PC_ACL_EXTENDED as i32;
+    #[cfg(target_os = "macos")]
+    pub const PC_EXTENDED_SECURITY_NP: i32 = super::_PC_EXTENDED_SECURITY_NP as i32;
 
     #[test]
     fn test_signed() {
@@ -0,0 +1,359 @@
+//! Async API that runs exacl calls on tokio's blocking thread pool.
+//!
+//! Every exacl call is a blocking system call, and name lookups may block
+//! for a long time when users and groups come from a network directory.
+//! The functions in this module run the calls with
+//! [`spawn_blocking`](::tokio::task::spawn_blocking). At most
+//! [`DEFAULT_MAX_CONCURRENCY`] calls run at the same time; further calls wait
+//! for a free slot. Use [`set_max_concurrency`] to change the limit.
+//!
+//! These functions must be called from within a tokio runtime.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use tokio_stream::StreamExt;
+//!
+//! let entries = exacl::tokio::getfacl("./tmp/foo", None).await?;
+//!
+//! let mut stream = exacl::tokio::getfacl_tree("./tmp", None);
+//! while let Some(result) = stream.next().await {
+//!     let (path, entries) = result?;
+//! }
+//! # Ok(()) }
+//! ```
+
+use crate::acl::AclOption;
+use crate::aclentry::AclEntry;
+use crate::diff::is_default;
+use crate::policy::{AclPolicy, PolicyViolation};
+use crate::walk::Walk;
+
+use ::tokio::runtime::Handle;
+use ::tokio::sync::{mpsc, Semaphore};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+
+/// Default maximum number of calls that run on the blocking thread pool at
+/// once.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 16;
+
+// Number of results buffered by `getfacl_tree` before the walk pauses.
+const STREAM_BUFFER: usize = 64;
+
+static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+
+/// Set the maximum number of calls that run on the blocking thread pool at
+/// once.
+///
+/// The limit applies to all functions in this module. It must be set before
+/// the first call to any of them.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if `limit` is zero, or if the limit is already in
+/// use.
+pub fn set_max_concurrency(limit: usize) -> io::Result<()> {
+    if limit == 0 {
+        return crate::failx::fail_custom("concurrency limit must not be zero");
+    }
+    PERMITS
+        .set(Semaphore::new(limit))
+        .or_else(|_| crate::failx::fail_custom("concurrency limit is already in use"))
+}
+
+/// Return the semaphore that limits concurrent calls.
+fn permits() -> &'static Semaphore {
+    PERMITS.get_or_init(|| Semaphore::new(DEFAULT_MAX_CONCURRENCY))
+}
+
+/// Run a blocking function on the blocking thread pool.
+async fn run_blocking<T, F>(func: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let permit = permits().acquire().await.map_err(io::Error::other)?;
+    ::tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        func()
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Get the access control list (ACL) for a file or directory.
+///
+/// See [`crate::getfacl`].
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub async fn getfacl<P, O>(path: P, options: O) -> io::Result<Vec<AclEntry>>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let path = path.as_ref().to_path_buf();
+    let options = options.into();
+    run_blocking(move || crate::getfacl(path, options)).await
+}
+
+/// Set the access control list (ACL) for specified files and directories.
+///
+/// See [`crate::setfacl`].
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub async fn setfacl<P, O>(paths: &[P], entries: &[AclEntry], options: O) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let paths = paths
+        .iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect::<Vec<_>>();
+    let entries = entries.to_vec();
+    let options = options.into();
+    run_blocking(move || crate::setfacl(&paths, &entries, options)).await
+}
+
+/// Return a stream with the ACL of every file in a directory tree.
+///
+/// Files are visited in sorted order; symlinks are not followed and have no
+/// result. An error for one file is returned as an item in the stream, and
+/// the walk continues. The walk runs on one blocking thread, and pauses if
+/// the stream is not read. Dropping the stream stops the walk.
+///
+/// Each step of the walk takes its own slot of the concurrency limit, so a
+/// paused walk does not hold a slot.
+pub fn getfacl_tree<P, O>(
+    root: P,
+    options: O,
+) -> impl Stream<Item = io::Result<(PathBuf, Vec<AclEntry>)>>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let root = root.as_ref().to_path_buf();
+    let options = options.into();
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    let errors = sender.clone();
+    let handle = Handle::current();
+
+    ::tokio::spawn(async move {
+        let walk =
+            ::tokio::task::spawn_blocking(move || walk_getfacl(&handle, &root, options, &sender));
+        let result = walk.await.unwrap_or_else(|err| Err(io::Error::other(err)));
+        if let Err(err) = result {
+            let _ = errors.send(Err(err)).await;
+        }
+    });
+
+    ReceiverStream::new(receiver)
+}
+
+/// Send the ACL of every file in the tree at `root` to `sender`.
+///
+/// A permit is held while the walk reads a directory or an ACL, but not while
+/// it waits for room in the channel.
+fn walk_getfacl(
+    handle: &Handle,
+    root: &Path,
+    options: Option<AclOption>,
+    sender: &mpsc::Sender<io::Result<(PathBuf, Vec<AclEntry>)>>,
+) -> io::Result<()> {
+    let mut walk = Walk::new(root);
+    loop {
+        let permit = handle
+            .block_on(permits().acquire())
+            .map_err(io::Error::other)?;
+        let result = match walk.next() {
+            None => return Ok(()),
+            Some(Ok(entry)) if entry.metadata.is_symlink() => continue,
+            Some(Ok(entry)) => crate::getfacl(&entry.path, options).map(|acl| (entry.path, acl)),
+            Some(Err(err)) => Err(err),
+        };
+        drop(permit);
+
+        if sender.blocking_send(result).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Set the access control list (ACL) of every file in a directory tree.
+///
+/// Symlinks are not followed and are left unchanged. Default entries are only
+/// set on directories; other files get the remaining entries. Returns the
+/// number of files changed. The tree is changed on one blocking thread.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. The walk stops at the first error.
+pub async fn setfacl_tree<P, O>(root: P, entries: &[AclEntry], options: O) -> io::Result<usize>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    let root = root.as_ref().to_path_buf();
+    let entries = entries.to_vec();
+    let options = options.into();
+    run_blocking(move || {
+        let file_entries = entries
+            .iter()
+            .filter(|entry| !is_default(entry))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut count = 0;
+        for item in Walk::new(&root) {
+            let entry = item?;
+            if entry.metadata.is_dir() {
+                crate::setfacl(&[&entry.path], &entries, options)?;
+            } else if !entry.metadata.is_symlink() {
+                crate::setfacl(&[&entry.path], &file_entries, options)?;
+            } else {
+                continue;
+            }
+            count += 1;
+        }
+        Ok(count)
+    })
+    .await
+}
+
+/// Check a tree against an [`AclPolicy`].
+///
+/// See [`AclPolicy::check`].
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub async fn check_policy<P>(policy: &AclPolicy, root: P) -> io::Result<Vec<PolicyViolation>>
+where
+    P: AsRef<Path>,
+{
+    let policy = policy.clone();
+    let root = root.as_ref().to_path_buf();
+    run_blocking(move || policy.check(root)).await
+}
+
+/// Correct the violations of an [`AclPolicy`] in a tree.
+///
+/// See [`AclPolicy::apply`].
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub async fn apply_policy<P>(policy: &AclPolicy, root: P) -> io::Result<Vec<PolicyViolation>>
+where
+    P: AsRef<Path>,
+{
+    let policy = policy.clone();
+    let root = root.as_ref().to_path_buf();
+    run_blocking(move || policy.apply(root)).await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tokio_tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    #[::tokio::test]
+    async fn test_getfacl_setfacl() -> io::Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let entries = getfacl(&file, None).await?;
+        setfacl(&[&file], &entries, None).await?;
+        assert_eq!(getfacl(&file, None).await?, entries);
+
+        let missing = file.path().with_extension("missing");
+        assert!(getfacl(&missing, None).await.is_err());
+
+        Ok(())
+    }
+
+    #[::tokio::test]
+    async fn test_getfacl_tree() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a"), "")?;
+        std::fs::create_dir(dir.path().join("b"))?;
+        std::os::unix::fs::symlink("a", dir.path().join("c"))?;
+
+        let results = getfacl_tree(dir.path(), None)
+            .collect::<io::Result<Vec<_>>>()
+            .await?;
+        let paths = results.into_iter().map(|item| item.0).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                dir.path().to_path_buf(),
+                dir.path().join("a"),
+                dir.path().join("b")
+            ]
+        );
+
+        let missing = getfacl_tree(dir.path().join("missing"), None)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(missing.len(), 1);
+        assert!(missing[0].is_err());
+
+        Ok(())
+    }
+
+    #[::tokio::test]
+    async fn test_getfacl_tree_unread() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        for i in 0..2 * STREAM_BUFFER {
+            std::fs::write(dir.path().join(i.to_string()), "")?;
+        }
+
+        // Walks that wait for their streams to be read hold no permits.
+        let streams = (0..2 * DEFAULT_MAX_CONCURRENCY)
+            .map(|_| getfacl_tree(dir.path(), None))
+            .collect::<Vec<_>>();
+        ::tokio::task::yield_now().await;
+        for _ in 0..STREAM_BUFFER {
+            getfacl(dir.path(), None).await?;
+        }
+        drop(streams);
+
+        assert!(set_max_concurrency(0).is_err());
+        assert!(set_max_concurrency(4).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    #[::tokio::test]
+    async fn test_setfacl_tree() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a"), "")?;
+        std::fs::create_dir(dir.path().join("b"))?;
+
+        let entries = crate::from_mode(0o750);
+        assert_eq!(setfacl_tree(dir.path(), &entries, None).await?, 3);
+        assert_eq!(getfacl(dir.path().join("a"), None).await?, entries);
+
+        // Default entries are only set on directories.
+        let mut inherited = entries.clone();
+        inherited.extend(entries.iter().map(|entry| AclEntry {
+            flags: crate::Flag::DEFAULT,
+            ..entry.clone()
+        }));
+        assert_eq!(setfacl_tree(dir.path(), &inherited, None).await?, 3);
+        assert_eq!(getfacl(dir.path().join("a"), None).await?, entries);
+        assert_eq!(getfacl(dir.path().join("b"), None).await?, inherited);
+
+        Ok(())
+    }
+}
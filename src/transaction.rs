@@ -0,0 +1,194 @@
+//! Implements transactional `setfacl` with rollback.
+
+use crate::acl::AclOption;
+use crate::aclentry::AclEntry;
+use crate::{_setfacl, drop_unknown, getfacl, PreparedAcl};
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Report of a failed transactional `setfacl`.
+///
+/// When [`setfacl`](crate::setfacl) is called with
+/// [`AclOption::TRANSACTIONAL`] and fails, the returned [`io::Error`] wraps a
+/// `RollbackReport`. Use [`RollbackReport::from_error`] to retrieve it.
+#[derive(Debug)]
+pub struct RollbackReport {
+    /// Path where `setfacl` failed.
+    pub failed_path: PathBuf,
+
+    /// Error that caused the rollback.
+    pub error: io::Error,
+
+    /// Paths whose previous ACL was restored.
+    pub restored: Vec<PathBuf>,
+
+    /// Paths whose previous ACL could not be restored, with the error.
+    pub rollback_failures: Vec<(PathBuf, io::Error)>,
+}
+
+impl RollbackReport {
+    /// Return true if every changed path was restored.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.rollback_failures.is_empty()
+    }
+
+    /// Return the report wrapped by an error from a transactional `setfacl`.
+    #[must_use]
+    pub fn from_error(err: &io::Error) -> Option<&RollbackReport> {
+        err.get_ref()?.downcast_ref::<RollbackReport>()
+    }
+}
+
+impl fmt::Display for RollbackReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}; rolled back {} path(s)",
+            self.error,
+            self.restored.len()
+        )?;
+        if !self.is_complete() {
+            write!(
+                f,
+                ", rollback failed for {} path(s)",
+                self.rollback_failures.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for RollbackReport {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Set the ACL of each path, restoring the previous ACL of every path already
+/// changed if any path fails.
+///
+/// The entries are checked first. If they do not form a valid ACL, the error
+/// is returned before any path is changed, without a `RollbackReport`.
+pub(crate) fn setfacl_transactional<P>(
+    paths: &[P],
+    entries: &[AclEntry],
+    options: AclOption,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let options = options - AclOption::TRANSACTIONAL;
+    let acl = PreparedAcl::new(entries, options)?;
+    let mut saved = Vec::<(&Path, Vec<AclEntry>)>::with_capacity(paths.len());
+
+    for path in paths {
+        let path = path.as_ref();
+
+        let prior = match getfacl(path, options) {
+            Ok(prior) => prior,
+            Err(err) => return Err(rollback(&saved, path, err, options)),
+        };
+        saved.push((path, prior));
+
+        // The failing path is rolled back too, since it may be partially
+        // changed.
+        if let Err(err) = acl.write(path, options) {
+            return Err(rollback(&saved, path, err, options));
+        }
+    }
+
+    Ok(())
+}
+
+/// Restore saved ACLs in reverse order and return an error with the report.
+fn rollback(
+    saved: &[(&Path, Vec<AclEntry>)],
+    failed_path: &Path,
+    error: io::Error,
+    options: AclOption,
+) -> io::Error {
+    let mut report = RollbackReport {
+        failed_path: failed_path.to_path_buf(),
+        error,
+        restored: Vec::with_capacity(saved.len()),
+        rollback_failures: Vec::new(),
+    };
+
+    for (path, prior) in saved.iter().rev() {
+        // Filter the saved entries the same way `setfacl` filters new ones.
+        let result = if options.contains(AclOption::DROP_UNKNOWN) {
+            _setfacl(&[path], &drop_unknown(prior), options)
+        } else {
+            _setfacl(&[path], prior, options)
+        };
+        match result {
+            Ok(()) => report.restored.push(path.to_path_buf()),
+            Err(err) => report.rollback_failures.push((path.to_path_buf(), err)),
+        }
+    }
+
+    io::Error::new(report.error.kind(), report)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, any(target_os = "linux", target_os = "freebsd")))]
+mod transaction_tests {
+    use super::*;
+    use crate::{from_mode, setfacl};
+
+    #[test]
+    fn test_setfacl_transactional() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file1 = dir.path().join("file1");
+        let file2 = dir.path().join("file2");
+        std::fs::write(&file1, "")?;
+        std::fs::write(&file2, "")?;
+        setfacl(&[&file1, &file2], &from_mode(0o640), None)?;
+        let before = getfacl(&file1, None)?;
+
+        setfacl(&[&file1], &from_mode(0o600), AclOption::TRANSACTIONAL)?;
+        assert_ne!(getfacl(&file1, None)?, before);
+        setfacl(&[&file1], &before, None)?;
+
+        let missing = dir.path().join("missing");
+        let paths = [&file1, &file2, &missing];
+        let err = setfacl(&paths, &from_mode(0o600), AclOption::TRANSACTIONAL).unwrap_err();
+        let report = RollbackReport::from_error(&err).unwrap();
+        assert_eq!(report.failed_path, missing);
+        assert_eq!(report.restored, [file2.clone(), file1.clone()]);
+        assert!(report.is_complete());
+        assert!(err
+            .to_string()
+            .ends_with("No such file or directory (os error 2); rolled back 2 path(s)"));
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        assert_eq!(getfacl(&file1, None)?, before);
+        assert_eq!(getfacl(&file2, None)?, before);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_setfacl_transactional_invalid() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        std::fs::write(&file, "")?;
+        setfacl(&[&file], &from_mode(0o640), None)?;
+        let before = getfacl(&file, None)?;
+
+        // Invalid entries fail before any path is touched.
+        let mut entries = from_mode(0o600);
+        entries.pop();
+        let err = setfacl(&[&file], &entries, AclOption::TRANSACTIONAL).unwrap_err();
+        assert!(RollbackReport::from_error(&err).is_none());
+        assert!(err.to_string().starts_with("Invalid ACL"));
+        assert_eq!(getfacl(&file, None)?, before);
+
+        Ok(())
+    }
+}
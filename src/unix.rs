@@ -116,7 +116,14 @@ pub fn name_to_gid(name: &str) -> io::Result<gid_t> {
 }
 
 /// Convert uid to user name.
+///
+/// If the uid has no user name, return the uid as a decimal string.
 pub fn uid_to_name(uid: uid_t) -> io::Result<String> {
+    Ok(find_user_name(uid)?.unwrap_or_else(|| uid.to_string()))
+}
+
+/// Return the user name for a uid, or `None` if the uid has no name.
+pub fn find_user_name(uid: uid_t) -> io::Result<Option<String>> {
     let mut pwd = mem::MaybeUninit::<passwd>::uninit();
     let mut buf = Vec::<c_char>::with_capacity(INITIAL_BUFSIZE);
     let mut result = ptr::null_mut();
@@ -147,14 +154,21 @@ pub fn uid_to_name(uid: uid_t) -> io::Result<String> {
 
     if !result.is_null() {
         let cstr = unsafe { CStr::from_ptr(pwd.assume_init().pw_name) };
-        return Ok(cstr.to_string_lossy().into_owned());
+        return Ok(Some(cstr.to_string_lossy().into_owned()));
     }
 
-    Ok(uid.to_string())
+    Ok(None)
 }
 
 /// Convert gid to group name.
+///
+/// If the gid has no group name, return the gid as a decimal string.
 pub fn gid_to_name(gid: gid_t) -> io::Result<String> {
+    Ok(find_group_name(gid)?.unwrap_or_else(|| gid.to_string()))
+}
+
+/// Return the group name for a gid, or `None` if the gid has no name.
+pub fn find_group_name(gid: gid_t) -> io::Result<Option<String>> {
     let mut grp = mem::MaybeUninit::<group>::uninit();
     let mut buf = Vec::<c_char>::with_capacity(INITIAL_BUFSIZE);
     let mut result = ptr::null_mut();
@@ -185,10 +199,10 @@ pub fn gid_to_name(gid: gid_t) -> io::Result<String> {
 
     if !result.is_null() {
         let cstr = unsafe { CStr::from_ptr(grp.assume_init().gr_name) };
-        return Ok(cstr.to_string_lossy().into_owned());
+        return Ok(Some(cstr.to_string_lossy().into_owned()));
     }
 
-    Ok(gid.to_string())
+    Ok(None)
 }
 
 /// Convert uid to GUID.
@@ -322,6 +336,8 @@ mod unix_tests {
     #[test]
     fn test_uid_to_name() {
         assert_eq!(uid_to_name(1500).unwrap(), "1500");
+        assert_eq!(find_user_name(1500).unwrap(), None);
+        assert_eq!(find_user_name(0).unwrap().unwrap(), "root");
 
         #[cfg(target_os = "macos")]
         assert_eq!(uid_to_name(89).unwrap(), "_spotlight");
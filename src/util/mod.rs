@@ -8,6 +8,7 @@
 //!    `xacl_init`      - create a new empty ACL
 //!    `xacl_free`      - destroy ACL
 //!    `xacl_foreach`   - apply a function to each entry in an ACL
+//!    `xacl_next_entry` - return the first or next entry in an ACL
 //!    `xacl_is_empty`  - return true if an ACL is empty
 //!    `xacl_is_posix`  - return true if ACL has Posix.1e semantics.
 //!    `xacl_add_entry` - append new entry to an ACL
@@ -15,6 +16,8 @@
 //!    `xacl_get_file`  - get ACL from file path
 //!    `xacl_set_file`  - set ACL for file path
 //!    `xacl_is_nfs4`   - return true if file path uses `NFSv4` ACL on `FreeBSD`
+//!    `xacl_acl_support` - return the kind of ACL supported for file path
+//!    `xacl_has_extended` - return true if file path has an ACL beyond its mode
 
 mod util_common;
 
@@ -32,18 +35,19 @@ pub use crate::sys::{acl_entry_t, acl_t};
 
 #[cfg(target_os = "freebsd")]
 pub use util_freebsd::{
-    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file, xacl_init,
-    xacl_is_empty, xacl_is_nfs4, xacl_is_posix, xacl_set_file,
+    xacl_acl_support, xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file,
+    xacl_has_extended, xacl_init, xacl_is_empty, xacl_is_nfs4, xacl_is_posix, xacl_next_entry,
+    xacl_set_file,
 };
 
 #[cfg(target_os = "linux")]
 pub use util_linux::{
-    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file, xacl_init,
-    xacl_is_empty, xacl_is_posix, xacl_set_file,
+    xacl_acl_support, xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file,
+    xacl_has_extended, xacl_init, xacl_is_empty, xacl_is_posix, xacl_next_entry, xacl_set_file,
 };
 
 #[cfg(target_os = "macos")]
 pub use util_macos::{
-    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file, xacl_init,
-    xacl_is_empty, xacl_is_posix, xacl_set_file,
+    xacl_acl_support, xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file,
+    xacl_has_extended, xacl_init, xacl_is_empty, xacl_is_posix, xacl_next_entry, xacl_set_file,
 };
@@ -39,22 +39,38 @@ pub fn xacl_foreach<F: FnMut(acl_entry_t) -> io::Result<()>>(
     acl: acl_t,
     mut func: F,
 ) -> io::Result<()> {
-    let mut entry: acl_entry_t = ptr::null_mut();
-    let mut entry_id = sg::ACL_FIRST_ENTRY;
+    let mut first = true;
 
-    assert!(!acl.is_null());
-    loop {
-        if !xacl_get_entry(acl, entry_id, &mut entry) {
-            break;
-        }
-        assert!(!entry.is_null());
+    while let Some(entry) = xacl_next_entry(acl, first) {
         func(entry)?;
-        entry_id = sg::ACL_NEXT_ENTRY;
+        first = false;
     }
 
     Ok(())
 }
 
+/// Return the first entry in a native ACL, or the entry after the one
+/// returned by the previous call.
+///
+/// The position is stored in the native ACL, so only one caller may iterate
+/// over an ACL at a time.
+pub fn xacl_next_entry(acl: acl_t, first: bool) -> Option<acl_entry_t> {
+    let mut entry: acl_entry_t = ptr::null_mut();
+    let entry_id = if first {
+        sg::ACL_FIRST_ENTRY
+    } else {
+        sg::ACL_NEXT_ENTRY
+    };
+
+    assert!(!acl.is_null());
+    if !xacl_get_entry(acl, entry_id, &mut entry) {
+        return None;
+    }
+    assert!(!entry.is_null());
+
+    Some(entry)
+}
+
 /// Create a new empty ACL with the given capacity.
 ///
 /// Client must call `xacl_free` when done with result.
@@ -3,6 +3,7 @@ use crate::failx::*;
 use crate::flag::Flag;
 use crate::perm::Perm;
 use crate::qualifier::Qualifier;
+use crate::support::AclSupport;
 use crate::sys::*;
 use crate::util::util_common;
 
@@ -14,7 +15,9 @@ use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use std::ptr;
 
-pub use util_common::{xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty};
+pub use util_common::{
+    xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty, xacl_next_entry,
+};
 
 use util_common::*;
 
@@ -477,6 +480,50 @@ pub fn xacl_is_nfs4(path: &Path, symlink: bool) -> io::Result<bool> {
     Ok(ret == 1)
 }
 
+/// Return true if the file at `path` has an ACL beyond its mode bits.
+pub fn xacl_has_extended(path: &Path) -> io::Result<bool> {
+    let acl = xacl_get_file(path, false, false)?;
+    defer! { xacl_free(acl) };
+
+    let mut trivial = 0;
+    let ret = unsafe { acl_is_trivial_np(acl, &mut trivial) };
+    if ret != 0 {
+        return fail_err(ret, "acl_is_trivial_np", ());
+    }
+
+    if trivial == 0 {
+        return Ok(true);
+    }
+
+    // A directory may also have a default ACL.
+    if xacl_is_posix(acl) && path.is_dir() {
+        let default_acl = xacl_get_file(path, false, true)?;
+        defer! { xacl_free(default_acl) };
+        return Ok(!xacl_is_empty(default_acl));
+    }
+
+    Ok(false)
+}
+
+/// Return the kind of ACL supported by the file system at `path`.
+pub fn xacl_acl_support(path: &Path) -> io::Result<AclSupport> {
+    if xacl_is_nfs4(path, false)? {
+        return Ok(AclSupport::Nfs4);
+    }
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let ret = unsafe { pathconf(c_path.as_ptr(), sg::PC_ACL_EXTENDED) };
+    if ret < 0 {
+        return fail_err(ret, "pathconf", &c_path);
+    }
+
+    if ret == 1 {
+        Ok(AclSupport::Posix)
+    } else {
+        Ok(AclSupport::None)
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -2,6 +2,7 @@ use crate::failx::*;
 use crate::flag::Flag;
 use crate::perm::Perm;
 use crate::qualifier::Qualifier;
+use crate::support::AclSupport;
 use crate::sys::*;
 use crate::util::util_common;
 
@@ -11,7 +12,9 @@ use std::io;
 use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 
-pub use util_common::{xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty};
+pub use util_common::{
+    xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty, xacl_next_entry,
+};
 
 use util_common::*;
 
@@ -201,6 +204,155 @@ pub const fn xacl_is_posix(_acl: acl_t) -> bool {
     true
 }
 
+/// Return true if the file system supports the extended attribute `name`.
+fn xattr_supported(c_path: &CString, name: &[u8]) -> io::Result<bool> {
+    let c_name = CString::new(name)?;
+    let ret = unsafe { getxattr(c_path.as_ptr(), c_name.as_ptr(), std::ptr::null_mut(), 0) };
+    if ret >= 0 {
+        return Ok(true);
+    }
+
+    match io::Error::last_os_error().raw_os_error() {
+        // The attribute is supported, but not set on this file.
+        Some(sg::ENODATA) => Ok(true),
+        Some(sg::ENOTSUP) => Ok(false),
+        _ => fail_err(ret, "getxattr", c_path),
+    }
+}
+
+// Size of the header and of each entry in a POSIX ACL extended attribute.
+const XATTR_ACL_HEADER_SIZE: usize = 4;
+const XATTR_ACL_ENTRY_SIZE: usize = 8;
+
+/// Return the size of the extended attribute `name`, or None if it is not
+/// set or not supported.
+fn xattr_size(c_path: &CString, name: &[u8]) -> io::Result<Option<usize>> {
+    let c_name = CString::new(name)?;
+    let ret = unsafe { getxattr(c_path.as_ptr(), c_name.as_ptr(), std::ptr::null_mut(), 0) };
+    if let Ok(size) = usize::try_from(ret) {
+        return Ok(Some(size));
+    }
+
+    match io::Error::last_os_error().raw_os_error() {
+        Some(sg::ENODATA | sg::ENOTSUP) => Ok(None),
+        _ => fail_err(ret, "getxattr", c_path),
+    }
+}
+
+/// Return the value of the extended attribute `name`, or None if it is not
+/// set or not supported.
+fn xattr_value(c_path: &CString, name: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let Some(size) = xattr_size(c_path, name)? else {
+        return Ok(None);
+    };
+
+    let c_name = CString::new(name)?;
+    let mut value = vec![0u8; size];
+    let ret = unsafe {
+        getxattr(
+            c_path.as_ptr(),
+            c_name.as_ptr(),
+            value.as_mut_ptr().cast::<c_void>(),
+            value.len(),
+        )
+    };
+    let Ok(len) = usize::try_from(ret) else {
+        return fail_err(ret, "getxattr", c_path);
+    };
+
+    value.truncate(len);
+    Ok(Some(value))
+}
+
+// NFSv4 principals that mirror the owner, group and other mode bits.
+const NFS4_SPECIAL_WHO: [&[u8]; 3] = [b"OWNER@", b"GROUP@", b"EVERYONE@"];
+
+// NFSv4 ACE flags that make an entry inheritable.
+const NFS4_INHERIT_FLAGS: u32 = 0x0f;
+
+/// Return true if an NFSv4 ACL, in the XDR form of the `system.nfs4_acl`
+/// attribute, goes beyond the mode bits.
+///
+/// The ACL that an NFS server derives from the mode bits only has entries
+/// for `OWNER@`, `GROUP@` and `EVERYONE@`, so an entry for any other
+/// principal, or an inheritable entry, makes the ACL extended.
+fn nfs4_xattr_is_extended(value: &[u8]) -> io::Result<bool> {
+    let mut buf = value;
+
+    let count = xdr_u32(&mut buf)?;
+    for _ in 0..count {
+        let _ace_type = xdr_u32(&mut buf)?;
+        let flags = xdr_u32(&mut buf)?;
+        let _access_mask = xdr_u32(&mut buf)?;
+        let who_len = xdr_u32(&mut buf)? as usize;
+        let who = xdr_take(&mut buf, who_len.next_multiple_of(4))?;
+
+        if flags & NFS4_INHERIT_FLAGS != 0 || !NFS4_SPECIAL_WHO.contains(&&who[..who_len]) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Remove `len` bytes from the front of an XDR buffer and return them.
+fn xdr_take<'a>(buf: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if buf.len() < len {
+        return fail_custom("invalid system.nfs4_acl attribute");
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+/// Remove a big-endian `u32` from the front of an XDR buffer.
+fn xdr_u32(buf: &mut &[u8]) -> io::Result<u32> {
+    let bytes = xdr_take(buf, 4)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Return true if the file at `path` has an ACL beyond its mode bits.
+///
+/// For a POSIX ACL, only the sizes of the ACL extended attributes are
+/// checked. An access ACL with more than three entries, or any default ACL,
+/// is extended. An NFSv4 ACL, as found on NFS mounts, is read from the
+/// `system.nfs4_acl` attribute; it is extended if it has entries that the mode
+/// bits cannot express.
+pub fn xacl_has_extended(path: &Path) -> io::Result<bool> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let min_size = XATTR_ACL_HEADER_SIZE + 3 * XATTR_ACL_ENTRY_SIZE;
+
+    if xattr_size(&c_path, b"system.posix_acl_access")?.is_some_and(|size| size > min_size) {
+        return Ok(true);
+    }
+
+    let default_size = xattr_size(&c_path, b"system.posix_acl_default")?;
+    if default_size.is_some_and(|size| size > XATTR_ACL_HEADER_SIZE) {
+        return Ok(true);
+    }
+
+    match xattr_value(&c_path, b"system.nfs4_acl")? {
+        Some(value) => nfs4_xattr_is_extended(&value),
+        None => Ok(false),
+    }
+}
+
+/// Return the kind of ACL supported by the file system at `path`.
+///
+/// Linux stores ACL's in extended attributes, so probe for the attribute
+/// used by each kind.
+pub fn xacl_acl_support(path: &Path) -> io::Result<AclSupport> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+
+    if xattr_supported(&c_path, b"system.posix_acl_access")? {
+        Ok(AclSupport::Posix)
+    } else if xattr_supported(&c_path, b"system.nfs4_acl")? {
+        Ok(AclSupport::Nfs4)
+    } else {
+        Ok(AclSupport::None)
+    }
+}
+
 #[cfg(test)]
 mod util_linux_test {
     use super::*;
@@ -231,6 +383,40 @@ mod util_linux_test {
         xacl_free(acl);
     }
 
+    #[test]
+    fn test_nfs4_xattr_is_extended() {
+        fn ace(flags: u32, who: &[u8]) -> Vec<u8> {
+            let mut result = Vec::new();
+            for word in [0, flags, 0x1f01ff, who.len() as u32] {
+                result.extend(word.to_be_bytes());
+            }
+            result.extend(who);
+            result.resize(result.len().next_multiple_of(4), 0);
+            result
+        }
+        let acl = |aces: &[Vec<u8>]| {
+            let mut result = (aces.len() as u32).to_be_bytes().to_vec();
+            result.extend(aces.concat());
+            result
+        };
+
+        let owner = ace(0, b"OWNER@");
+        let group = ace(0x40, b"GROUP@");
+        let everyone = ace(0, b"EVERYONE@");
+        let trivial = acl(&[owner.clone(), group, everyone.clone()]);
+        assert!(!nfs4_xattr_is_extended(&trivial).unwrap());
+
+        let named = acl(&[owner.clone(), ace(0, b"alice@example.com"), everyone]);
+        assert!(nfs4_xattr_is_extended(&named).unwrap());
+
+        let inherited = acl(&[owner, ace(0x03, b"GROUP@")]);
+        assert!(nfs4_xattr_is_extended(&inherited).unwrap());
+
+        let err = nfs4_xattr_is_extended(&trivial[..trivial.len() - 4]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid system.nfs4_acl attribute");
+        assert!(nfs4_xattr_is_extended(&[]).is_err());
+    }
+
     #[test]
     fn test_empty_acl() {
         let file = tempfile::NamedTempFile::new().unwrap();
@@ -3,6 +3,7 @@ use crate::failx::*;
 use crate::flag::Flag;
 use crate::perm::Perm;
 use crate::qualifier::Qualifier;
+use crate::support::AclSupport;
 use crate::sys::*;
 use crate::util::util_common;
 
@@ -13,7 +14,9 @@ use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use uuid::Uuid;
 
-pub use util_common::{xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty};
+pub use util_common::{
+    xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty, xacl_next_entry,
+};
 
 use util_common::*;
 
@@ -256,6 +259,31 @@ pub const fn xacl_is_posix(_acl: acl_t) -> bool {
     false
 }
 
+/// Return true if the file at `path` has an extended ACL.
+pub fn xacl_has_extended(path: &Path) -> io::Result<bool> {
+    let acl = xacl_get_file(path, false, false)?;
+    defer! { xacl_free(acl) };
+
+    Ok(!xacl_is_empty(acl))
+}
+
+/// Return the kind of ACL supported by the file system at `path`.
+///
+/// macOS extended ACL's have NFSv4 semantics.
+pub fn xacl_acl_support(path: &Path) -> io::Result<AclSupport> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let ret = unsafe { pathconf(c_path.as_ptr(), sg::PC_EXTENDED_SECURITY_NP) };
+    if ret < 0 {
+        return fail_err(ret, "pathconf", &c_path);
+    }
+
+    if ret == 1 {
+        Ok(AclSupport::Nfs4)
+    } else {
+        Ok(AclSupport::None)
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -0,0 +1,121 @@
+//! Implements a recursive directory walk shared by the tree APIs.
+
+use crate::failx::path_err;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A file visited by [`Walk`].
+pub(crate) struct WalkEntry {
+    /// Path of the file, starting with the root of the walk.
+    pub path: PathBuf,
+
+    /// Path of the file relative to the root. Empty for the root itself.
+    pub relative: PathBuf,
+
+    /// Metadata of the file. Symlinks are not followed.
+    pub metadata: fs::Metadata,
+}
+
+/// Depth-first, pre-order walk of a directory tree.
+///
+/// The entries of each directory are visited in sorted order, so the walk
+/// is deterministic. Symlinks are reported but never followed.
+pub(crate) struct Walk {
+    root: PathBuf,
+    stack: Vec<PathBuf>,
+    pending_dir: Option<PathBuf>,
+}
+
+impl Walk {
+    /// Create a walk of the tree starting at `root`.
+    pub fn new(root: &Path) -> Walk {
+        Walk {
+            root: root.to_path_buf(),
+            stack: vec![PathBuf::new()],
+            pending_dir: None,
+        }
+    }
+
+    /// Return the full path for a path relative to the root.
+    fn full_path(&self, relative: &Path) -> PathBuf {
+        if relative.as_os_str().is_empty() {
+            self.root.clone()
+        } else {
+            self.root.join(relative)
+        }
+    }
+
+    /// Push the sorted contents of a directory onto the stack.
+    fn read_dir(&mut self, relative: &Path) -> io::Result<()> {
+        let path = self.full_path(relative);
+        let mut names = fs::read_dir(&path)
+            .and_then(|dir| {
+                dir.map(|item| item.map(|entry| entry.file_name()))
+                    .collect::<io::Result<Vec<_>>>()
+            })
+            .map_err(|err| path_err(&path, &err))?;
+
+        names.sort();
+        self.stack
+            .extend(names.into_iter().rev().map(|name| relative.join(name)));
+        Ok(())
+    }
+}
+
+impl Iterator for Walk {
+    type Item = io::Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(dir) = self.pending_dir.take() {
+            if let Err(err) = self.read_dir(&dir) {
+                return Some(Err(err));
+            }
+        }
+
+        let relative = self.stack.pop()?;
+        let path = self.full_path(&relative);
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err) => return Some(Err(path_err(&path, &err))),
+        };
+
+        if metadata.is_dir() {
+            self.pending_dir = Some(relative.clone());
+        }
+
+        Some(Ok(WalkEntry {
+            path,
+            relative,
+            metadata,
+        }))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod walk_tests {
+    use super::*;
+
+    #[test]
+    fn test_walk_order() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir_all(dir.path().join("b/d"))?;
+        fs::write(dir.path().join("b/c"), "")?;
+        fs::write(dir.path().join("a"), "")?;
+        std::os::unix::fs::symlink("b", dir.path().join("e"))?;
+
+        let visited = Walk::new(dir.path())
+            .map(|entry| entry.map(|entry| entry.relative))
+            .collect::<io::Result<Vec<_>>>()?;
+        let expected = ["", "a", "b", "b/c", "b/d", "e"].map(PathBuf::from);
+        assert_eq!(visited, expected);
+
+        let err = Walk::new(&dir.path().join("missing")).next().unwrap();
+        assert!(err.is_err());
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 //! API Tests for exacl module.
 
 use ctor::ctor;
-use exacl::{getfacl, setfacl, AclEntry, AclOption, Perm};
+use exacl::{getfacl, setfacl, AclEntry, AclOption, FileAcl, FileKind, Perm};
 use log::debug;
 use std::io;
 
@@ -70,8 +70,29 @@ fn test_setfacl_file() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_fileacl_read() -> io::Result<()> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let dir = tempfile::tempdir()?;
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o750))?;
+
+    let acl = FileAcl::read(&dir, None)?;
+    let metadata = std::fs::metadata(&dir)?;
+    assert_eq!(acl.path, dir.path());
+    assert_eq!(acl.file_type, FileKind::Directory);
+    assert_eq!(acl.uid, metadata.uid());
+    assert_eq!(acl.gid, metadata.gid());
+    assert_eq!(acl.mode, 0o750);
+    assert_eq!(acl.entries(), getfacl(&dir, None)?);
+    assert_eq!(acl.to_string().parse::<FileAcl>().unwrap(), acl);
+
+    Ok(())
+}
+
 /// Get the type of filesystem from `df -Th` command output.
 #[cfg(target_os = "linux")]
+#[allow(clippy::zombie_processes)]
 fn get_filesystem(path: &std::path::PathBuf) -> String {
     let df = std::process::Command::new("df")
         .arg("-Th")
@@ -196,6 +217,34 @@ allow::user:ccc:read,execute
     Ok(())
 }
 
+#[test]
+fn test_setfacl_drop_unknown() -> io::Result<()> {
+    use exacl::AclEntryKind;
+
+    let file = tempfile::NamedTempFile::new()?;
+    let mut entries = getfacl(&file, None)?;
+    let expected = entries.clone();
+    entries.push(AclEntry {
+        kind: AclEntryKind::Unknown,
+        name: "@tag 42".to_string(),
+        perms: Perm::READ,
+        flags: exacl::Flag::empty(),
+        allow: true,
+    });
+
+    let err = setfacl(&[&file], &entries, None).unwrap_err();
+    assert!(err.to_string().contains("unsupported kind: \"unknown\""));
+
+    setfacl(&[&file], &entries, AclOption::DROP_UNKNOWN)?;
+    assert_eq!(getfacl(&file, None)?, expected);
+
+    let (known, unknown) = exacl::split_unknown(&entries);
+    assert_eq!(known, expected);
+    assert_eq!(unknown, entries[expected.len()..]);
+
+    Ok(())
+}
+
 #[test]
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 fn test_exclusive_acloptions() {